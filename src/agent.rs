@@ -0,0 +1,283 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::boundary::{BoundaryStatus, TrackBoundary};
+use crate::car::Car;
+use crate::collision::{CarHit, ConeHit};
+use crate::env::{Action, Observation, StepInfo};
+use crate::params::VehicleParams;
+use crate::reward::RewardFunction;
+use crate::sensor::Sensor;
+use crate::snapshot::{AgentSnapshot, SnapshotError};
+use crate::timing::LapTimer;
+use crate::track::{ConeId, Track};
+
+const N_SECTORS: usize = 3;
+
+/// An `AgentSnapshot` with its reward function and sensors already read,
+/// from `Agent::load_snapshot`.
+pub struct RestoredAgent {
+    snapshot: AgentSnapshot,
+    reward: Option<Box<dyn RewardFunction>>,   // None keeps the reward function of the agent
+    sensors: Vec<Box<dyn Sensor>>,
+}
+
+/// One car on a track together with everything tracked per car: its reward
+/// function, lap timer, boundary status, contacts, its sensors and the random
+/// number generator of their noise. Every sensor samples at its own rate and
+/// delivers after its own latency. `Env` drives a single agent, `World` many
+/// on the same track.
+pub struct Agent {
+    car: Car,
+    vehicle_params: VehicleParams,
+    reward: Box<dyn RewardFunction>,
+    lap_timer: LapTimer,
+    boundary_status: BoundaryStatus,
+    cone_penalty: f32,
+    cone_contacts: Vec<ConeId>,
+    cone_hits: Vec<ConeHit>,
+    car_contacts: Vec<usize>,
+    car_hits: Vec<CarHit>,
+    sensors: Vec<Box<dyn Sensor>>,
+    rng: ChaCha8Rng,
+    time: f32,                  // s, the sensors are updated to
+    goal_direction: f32,        // rad, world angle of the farthest blue cone last seen
+}
+
+impl Agent {
+    /// The agent has to be reset on a track before its first step.
+    pub fn new(vehicle_params: VehicleParams, reward: Box<dyn RewardFunction>, track: &Track) -> Agent {
+        let (x, y, direction) = track.get_start_pose();
+        Agent {
+            car: Car::from_params(x, y, direction, &vehicle_params),
+            reward,
+            lap_timer: LapTimer::new(track, N_SECTORS, (x, y)),
+            boundary_status: BoundaryStatus::default(),
+            cone_penalty: 0.0,
+            cone_contacts: vec![],
+            cone_hits: vec![],
+            car_contacts: vec![],
+            car_hits: vec![],
+            sensors: vehicle_params.build_sensors(),
+            vehicle_params,
+            rng: ChaCha8Rng::seed_from_u64(0),
+            time: 0.0,
+            goal_direction: direction,
+        }
+    }
+
+    /// Places a new car at `pose` and clears everything recorded so far. The
+    /// sensor noise is drawn from a generator seeded with `seed`. Every
+    /// sensor takes its first sample right away.
+    pub fn reset(&mut self, track: &Track, boundary: &TrackBoundary, pose: (f32,f32,f32), seed: u64) {
+        self.car = Car::from_params(pose.0, pose.1, pose.2, &self.vehicle_params);
+        self.boundary_status = boundary.query(self.car.get_footprint().center);
+        self.lap_timer = LapTimer::new(track, N_SECTORS, (pose.0, pose.1));
+        self.reward.reset(track);
+        self.cone_contacts.clear();
+        self.cone_hits.clear();
+        self.car_contacts.clear();
+        self.car_hits.clear();
+        for sensor in self.sensors.iter_mut() {
+            sensor.reset();
+        }
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self.goal_direction = pose.2;
+        self.update_sensors(track, 0.0, 0.0);
+    }
+
+    /// Setup of the car, used from the next reset on. Replaces all sensors,
+    /// also the ones added with `add_sensor`.
+    pub fn set_vehicle_params(&mut self, vehicle_params: VehicleParams) {
+        self.sensors = vehicle_params.build_sensors();
+        self.vehicle_params = vehicle_params;
+    }
+
+    /// Mounts a sensor in addition to the ones of the vehicle parameters, it
+    /// takes its first sample with the next reset.
+    pub fn add_sensor(&mut self, sensor: Box<dyn Sensor>) {
+        self.sensors.push(sensor);
+    }
+
+    /// Subtracted from the reward for every cone hit.
+    pub fn set_cone_penalty(&mut self, cone_penalty: f32) {
+        self.cone_penalty = cone_penalty;
+    }
+
+    /// Moves the car from `time` by `dt` and records cone hits, the boundary
+    /// status, timing line crossings and the sensor data delivered meanwhile.
+    pub fn drive(&mut self, action: Action, track: &Track, boundary: &TrackBoundary, time: f32, dt: f32) -> StepInfo {
+        self.car.steering_command = action.steering;
+        self.car.throttle = action.throttle;
+        self.car.brake = action.brake;
+        let (from_x, from_y, _) = self.car.get_position();
+        self.car.update_car_position(dt);
+        let (to_x, to_y, _) = self.car.get_position();
+        let timing = self.lap_timer.update((from_x, from_y), (to_x, to_y), time, time + dt);
+
+        // a cone is hit once when the contact starts, not in every step it is touched,
+        // the new contacts go behind the previous ones until those are compared
+        let n_previous = self.cone_contacts.len();
+        self.cone_contacts.extend(self.car.get_footprint().colliding_cones(track));
+        let (previous, contacts) = self.cone_contacts.split_at(n_previous);
+        let cone_hits: Vec<ConeHit> = contacts.iter()
+            .filter(|cone| !previous.contains(cone))
+            .map(|cone| ConeHit { cone: *cone, time: time + dt })
+            .collect();
+        self.cone_contacts.drain(..n_previous);
+        self.cone_hits.extend_from_slice(&cone_hits);
+        self.boundary_status = boundary.query(self.car.get_footprint().center);
+        self.update_sensors(track, time + dt, dt);
+
+        StepInfo {
+            time: time + dt,
+            traveled_distance: self.car.get_traveled_distance(),
+            cone_hits,
+            car_hits: vec![],
+            boundary: self.boundary_status,
+            timing,
+        }
+    }
+
+    /// Advances every sensor by `dt` seconds up to `time`.
+    fn update_sensors(&mut self, track: &Track, time: f32, dt: f32) {
+        for sensor in self.sensors.iter_mut() {
+            sensor.update(self.car, track, time, dt, &mut self.rng);
+        }
+        self.time = time;
+    }
+
+    /// Records hits with the cars listed in `contacts` that were not touching
+    /// in the previous step.
+    pub fn update_car_contacts(&mut self, contacts: impl Iterator<Item = usize>, info: &mut StepInfo) {
+        let n_previous = self.car_contacts.len();
+        self.car_contacts.extend(contacts);
+        let (previous, contacts) = self.car_contacts.split_at(n_previous);
+        for car in contacts.iter() {
+            if !previous.contains(car) {
+                let hit = CarHit { car: *car, time: info.time };
+                info.car_hits.push(hit);
+                self.car_hits.push(hit);
+            }
+        }
+        self.car_contacts.drain(..n_previous);
+    }
+
+    /// Reward of the step described by `info`, including the cone penalty.
+    pub fn get_reward(&mut self, info: &StepInfo) -> f32 {
+        self.reward.get_reward(&self.car, info) - self.cone_penalty*(info.cone_hits.len() as f32)
+    }
+
+    /// Overwrites `observation` with what the sensors delivered, the cones of
+    /// the newest scan of every cone sensor and the other data since the last
+    /// observation. The buffers of `observation` are reused.
+    pub fn observe(&mut self, observation: &mut Observation) {
+        observation.clear();
+        observation.velocity = self.car.get_velocity();
+        observation.steering_angle = self.car.get_steering_angle();
+        for sensor in self.sensors.iter_mut() {
+            sensor.observe(observation);
+        }
+        let (_, _, direction) = self.car.get_position();
+        let farest_cone = observation.blue_cones.iter().max_by(|a, b| a.range.total_cmp(&b.range));
+        if let Some(cone) = farest_cone {
+            self.goal_direction = direction + cone.bearing;
+        }
+        let goal_bearing = self.goal_direction - direction;
+        observation.goal_bearing = goal_bearing.sin().atan2(goal_bearing.cos());
+    }
+
+    pub fn snapshot(&self) -> Result<AgentSnapshot, SnapshotError> {
+        Ok(AgentSnapshot {
+            car: self.car,
+            vehicle_params: self.vehicle_params.clone(),
+            reward: self.reward.snapshot()?,
+            lap_timer: self.lap_timer.clone(),
+            boundary_status: self.boundary_status,
+            cone_penalty: self.cone_penalty,
+            cone_contacts: self.cone_contacts.clone(),
+            cone_hits: self.cone_hits.clone(),
+            car_contacts: self.car_contacts.clone(),
+            car_hits: self.car_hits.clone(),
+            sensors: self.sensors.iter().map(|sensor| sensor.snapshot()).collect::<Result<_, _>>()?,
+            rng: self.rng.clone(),
+            time: self.time,
+            goal_direction: self.goal_direction,
+        })
+    }
+
+    /// Restores the state of `snapshot`, leaving the agent unchanged if the
+    /// reward function or a sensor can not read its part. The agent needs the
+    /// same number of sensors, of the types the snapshot was taken with.
+    pub fn restore(&mut self, snapshot: &AgentSnapshot) -> Result<(), SnapshotError> {
+        let restored = self.load_snapshot(snapshot)?;
+        self.apply_snapshot(restored);
+        Ok(())
+    }
+
+    /// Reads the reward function and the sensors of `snapshot` without
+    /// changing the agent, `apply_snapshot` then takes them over. This lets
+    /// several agents be restored all or nothing.
+    pub fn load_snapshot(&self, snapshot: &AgentSnapshot) -> Result<RestoredAgent, SnapshotError> {
+        if snapshot.sensors.len() != self.sensors.len() {
+            return Err(SnapshotError::SensorCount { expected: self.sensors.len(), found: snapshot.sensors.len() });
+        }
+        let sensors = self.sensors.iter()
+            .zip(snapshot.sensors.iter())
+            .map(|(sensor, state)| sensor.restore(state))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(RestoredAgent {
+            snapshot: snapshot.clone(),
+            reward: self.reward.restore(&snapshot.reward)?,
+            sensors,
+        })
+    }
+
+    /// Takes over a snapshot read by `load_snapshot`, this can not fail.
+    pub fn apply_snapshot(&mut self, restored: RestoredAgent) {
+        let snapshot = restored.snapshot;
+        if let Some(reward) = restored.reward {
+            self.reward = reward;
+        }
+        self.sensors = restored.sensors;
+        self.car = snapshot.car;
+        self.vehicle_params = snapshot.vehicle_params;
+        self.lap_timer = snapshot.lap_timer;
+        self.boundary_status = snapshot.boundary_status;
+        self.cone_penalty = snapshot.cone_penalty;
+        self.cone_contacts = snapshot.cone_contacts;
+        self.cone_hits = snapshot.cone_hits;
+        self.car_contacts = snapshot.car_contacts;
+        self.car_hits = snapshot.car_hits;
+        self.rng = snapshot.rng;
+        self.time = snapshot.time;
+        self.goal_direction = snapshot.goal_direction;
+    }
+
+    /// Ground truth state of the car, e.g. for rendering.
+    pub fn get_car(&self) -> Car {
+        self.car
+    }
+
+    pub fn get_sensors(&self) -> &[Box<dyn Sensor>] {
+        &self.sensors
+    }
+
+    pub fn get_boundary_status(&self) -> BoundaryStatus {
+        self.boundary_status
+    }
+
+    pub fn get_lap_timer(&self) -> &LapTimer {
+        &self.lap_timer
+    }
+
+    /// All cone hits since the last reset.
+    pub fn get_cone_hits(&self) -> &[ConeHit] {
+        &self.cone_hits
+    }
+
+    /// All hits with other cars since the last reset.
+    pub fn get_car_hits(&self) -> &[CarHit] {
+        &self.car_hits
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use crate::track::{Cone, ConeColor, Track};
+
+/// Where a point is relative to the track edges.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BoundaryStatus {
+    pub on_track: bool,
+    pub distance_to_blue: f32,      // m to the closest segment of the blue edge
+    pub distance_to_yellow: f32,    // m to the closest segment of the yellow edge
+}
+
+/// Track edges as closed polygons through the blue and the yellow cones.
+/// The area between the outer and the inner polygon is on track.
+#[derive(Clone)]
+pub struct TrackBoundary {
+    blue_edge: Vec<Cone>,
+    yellow_edge: Vec<Cone>,
+    blue_is_outer: bool,
+}
+
+impl TrackBoundary {
+    pub fn new(track: &Track) -> TrackBoundary {
+        TrackBoundary {
+            blue_edge: track.get_cones(ConeColor::Blue).to_vec(),
+            yellow_edge: track.get_cones(ConeColor::Yellow).to_vec(),
+            blue_is_outer: polygon_area(track.get_cones(ConeColor::Blue)) > polygon_area(track.get_cones(ConeColor::Yellow)),
+        }
+    }
+
+    pub fn query(&self, point: (f32,f32)) -> BoundaryStatus {
+        let point = (point.0 as f64, point.1 as f64);
+        let (outer, inner) = if self.blue_is_outer {
+            (&self.blue_edge, &self.yellow_edge)
+        } else {
+            (&self.yellow_edge, &self.blue_edge)
+        };
+        BoundaryStatus {
+            on_track: is_inside_polygon(outer, point) && !is_inside_polygon(inner, point),
+            distance_to_blue: distance_to_polygon(&self.blue_edge, point) as f32,
+            distance_to_yellow: distance_to_polygon(&self.yellow_edge, point) as f32,
+        }
+    }
+}
+
+/// Absolute area of a closed polygon (shoelace formula).
+fn polygon_area(polygon: &[Cone]) -> f64 {
+    let mut area = 0.0;
+    for (i, p1) in polygon.iter().enumerate() {
+        let p2 = polygon[(i+1) % polygon.len()];
+        area += p1.0*p2.1 - p2.0*p1.1;
+    }
+    (area/2.0).abs()
+}
+
+/// Even-odd rule: counts the edges crossed by a ray from `point` in +x direction.
+fn is_inside_polygon(polygon: &[Cone], point: (f64,f64)) -> bool {
+    let mut inside = false;
+    for (i, p1) in polygon.iter().enumerate() {
+        let p2 = polygon[(i+1) % polygon.len()];
+        if (p1.1 > point.1) != (p2.1 > point.1) {
+            let x_cross = p1.0 + (point.1-p1.1)/(p2.1-p1.1)*(p2.0-p1.0);
+            if point.0 < x_cross {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn distance_to_polygon(polygon: &[Cone], point: (f64,f64)) -> f64 {
+    let mut min_distance = f64::INFINITY;
+    for (i, p1) in polygon.iter().enumerate() {
+        let p2 = polygon[(i+1) % polygon.len()];
+        min_distance = min_distance.min(distance_to_segment(*p1, p2, point));
+    }
+    min_distance
+}
+
+fn distance_to_segment(p1: (f64,f64), p2: (f64,f64), point: (f64,f64)) -> f64 {
+    let dx = p2.0-p1.0;
+    let dy = p2.1-p1.1;
+    let length_squared = dx*dx + dy*dy;
+    let t = if length_squared > 0.0 {
+        (((point.0-p1.0)*dx + (point.1-p1.1)*dy)/length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    ((p1.0 + t*dx - point.0).powf(2.0) + (p1.1 + t*dy - point.1).powf(2.0)).sqrt()
+}
@@ -0,0 +1,186 @@
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::car::Car;
+use crate::env::Observation;
+use crate::sensor::{get_detection_probability, get_mount_pose, get_sector_outline, DelayLine, SampleTimer, Sensor};
+use crate::track::{ConeColor, Track, CONE_HEIGHT, CONE_RADIUS};
+
+/// Pinhole camera looking along its mounting yaw with a horizontal optical
+/// axis, and the errors of the cone detector running on its images.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraParams {
+    pub mount_x: f32,                           // m in front of the rear axle
+    pub mount_y: f32,                           // m towards increasing direction
+    pub mount_height: f32,                      // m above the ground
+    pub mount_yaw: f32,                         // rad, relative to the heading of the car
+    pub frame_rate: f32,                        // Hz
+    pub latency: f32,                           // s until the detections of a frame are delivered
+    pub image_width: f32,                       // px
+    pub image_height: f32,                      // px
+    pub focal_length: f32,                      // px
+    pub max_range: f32,                         // m, farther cones are too small to detect
+    pub detection_probability: f32,             // of a cone right in front of the camera
+    pub detection_probability_at_range: f32,    // of a cone at max_range, linear in between
+    pub misclassification_probability: f32,     // of reporting one of the other two colors
+    pub min_visible_fraction: f32,              // of a box inside the image, cut boxes below are dropped
+}
+
+impl Default for CameraParams {
+    fn default() -> CameraParams {
+        CameraParams {
+            mount_x: 0.5,
+            mount_y: 0.0,
+            mount_height: 1.0,
+            mount_yaw: 0.0,
+            frame_rate: 30.0,
+            latency: 0.0,
+            image_width: 1280.0,
+            image_height: 720.0,
+            focal_length: 800.0,
+            max_range: 30.0,
+            detection_probability: 1.0,
+            detection_probability_at_range: 0.5,
+            misclassification_probability: 0.02,
+            min_visible_fraction: 0.3,
+        }
+    }
+}
+
+/// Detected cone in image coordinates, x to the right and y down from the
+/// top left corner, clipped to the image.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub time: f32,          // s, when the frame was taken
+    pub sensor: usize,      // index of the camera among the sensors of the car
+    pub x_min: f32,         // px
+    pub y_min: f32,         // px
+    pub x_max: f32,         // px
+    pub y_max: f32,         // px
+    pub color: ConeColor,   // as classified, not necessarily the true color
+}
+
+impl CameraParams {
+    /// Horizontal field of view in rad.
+    pub fn get_horizontal_fov(self) -> f32 {
+        2.0*(self.image_width/2.0/self.focal_length).atan()
+    }
+
+    /// Adds the bounding boxes of the cones the detector finds in an image
+    /// taken at `time` by the camera with index `sensor` to `boxes`.
+    pub fn detect<R: Rng>(self, car: Car, track: &Track, sensor: usize, time: f32, rng: &mut R, boxes: &mut Vec<BoundingBox>) {
+        let (x, y, yaw) = get_mount_pose((self.mount_x, self.mount_y, self.mount_yaw), car);
+        // farthest a cone can be that is at most max_range ahead and inside the image
+        let reach = self.max_range/(self.get_horizontal_fov()/2.0).cos() + CONE_RADIUS as f32;
+        for (id, cone) in track.query_cones((x, y), reach) {
+            let x_diff = cone.0 as f32 - x;
+            let y_diff = cone.1 as f32 - y;
+            let forward = x_diff*yaw.cos() + y_diff*yaw.sin();
+            let left = -x_diff*yaw.sin() + y_diff*yaw.cos();
+            if forward <= CONE_RADIUS as f32 || forward > self.max_range {
+                continue;
+            }
+            let Some((x_min, y_min, x_max, y_max)) = self.project(forward, left) else {
+                continue;
+            };
+            let probability = get_detection_probability(self.detection_probability, self.detection_probability_at_range, forward, self.max_range);
+            if rng.gen::<f32>() >= probability {
+                continue;
+            }
+            let color = if rng.gen::<f32>() < self.misclassification_probability {
+                let mut others = [ConeColor::Blue, ConeColor::Yellow, ConeColor::Orange].into_iter()
+                    .filter(|color| *color != id.color);
+                others.nth(rng.gen_range(0..2)).unwrap_or(id.color)
+            } else {
+                id.color
+            };
+            boxes.push(BoundingBox { time, sensor, x_min, y_min, x_max, y_max, color });
+        }
+    }
+
+    /// Box of a cone `forward` m ahead of and `left` m beside the camera as
+    /// (x_min, y_min, x_max, y_max) clipped to the image, `None` if too little
+    /// of it is inside the image.
+    fn project(self, forward: f32, left: f32) -> Option<(f32,f32,f32,f32)> {
+        let center_x = self.image_width/2.0;
+        let center_y = self.image_height/2.0;
+        let scale = self.focal_length/forward;
+        let x_min = center_x - (left + CONE_RADIUS as f32)*scale;
+        let x_max = center_x - (left - CONE_RADIUS as f32)*scale;
+        let y_min = center_y - (CONE_HEIGHT as f32 - self.mount_height)*scale;
+        let y_max = center_y + self.mount_height*scale;
+        let clipped = (x_min.max(0.0), y_min.max(0.0), x_max.min(self.image_width), y_max.min(self.image_height));
+        let area = (x_max-x_min)*(y_max-y_min);
+        let visible_area = (clipped.2-clipped.0).max(0.0)*(clipped.3-clipped.1).max(0.0);
+        if area > 0.0 && visible_area/area >= self.min_visible_fraction.max(f32::EPSILON) {
+            Some(clipped)
+        } else {
+            None
+        }
+    }
+}
+
+/// Camera taking frames at its frame rate, the detections of a frame arrive
+/// after the latency.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Camera {
+    params: CameraParams,
+    sensor: usize,
+    timer: SampleTimer,
+    delay: DelayLine<BoundingBox>,
+    delivered: Vec<BoundingBox>,    // since the last observation
+    #[serde(skip)]
+    frame: Vec<BoundingBox>,        // detections of the frame being taken
+}
+
+impl Camera {
+    /// `sensor` is the index of the camera among the sensors of the car.
+    pub fn new(params: CameraParams, sensor: usize) -> Camera {
+        Camera {
+            params,
+            sensor,
+            timer: SampleTimer::new(params.frame_rate),
+            delay: DelayLine::new(params.latency),
+            delivered: vec![],
+            frame: vec![],
+        }
+    }
+
+    pub fn get_params(&self) -> CameraParams {
+        self.params
+    }
+
+}
+
+impl Sensor for Camera {
+    fn get_mount(&self) -> (f32,f32,f32) {
+        (self.params.mount_x, self.params.mount_y, self.params.mount_yaw)
+    }
+
+    fn get_field_of_view(&self) -> Vec<(f32,f32)> {
+        get_sector_outline(self.get_mount(), self.params.get_horizontal_fov(), self.params.max_range)
+    }
+
+    fn reset(&mut self) {
+        *self = Camera::new(self.params, self.sensor);
+    }
+
+    /// Takes a frame if one is due after `dt` seconds, at the time and from
+    /// the pose of the last frame due.
+    fn update(&mut self, car: Car, track: &Track, time: f32, dt: f32, rng: &mut ChaCha8Rng) {
+        if let Some(offset) = self.timer.advance(dt).last() {
+            let frame_time = time - dt + offset;
+            self.params.detect(car.extrapolate(offset - dt), track, self.sensor, frame_time, rng, &mut self.frame);
+            for bounding_box in self.frame.drain(..) {
+                self.delay.push(frame_time, bounding_box);
+            }
+        }
+        self.delay.deliver(time, &mut self.delivered);
+    }
+
+    fn observe(&mut self, observation: &mut Observation) {
+        observation.bounding_boxes.append(&mut self.delivered);
+    }
+}
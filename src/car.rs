@@ -1,154 +1,233 @@
+use serde::{Deserialize, Serialize};
 
+use crate::collision::Footprint;
+use crate::dynamics::{VehicleModel, BicycleParams, Powertrain, SteeringActuator, DYNAMIC_MIN_VELOCITY, GRAVITY, MAX_SUBSTEP};
+use crate::params::VehicleParams;
+use crate::sensor::Detection;
+use crate::track::Cone;
 
-#[derive(Copy, Clone)]
+
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct Car {
     pos_x:f32,
     pos_y:f32,
     direction: f32,
-    view_angle: f32,
-    view_range: f32,
-    pub velocity: f32,
-    pub steering_angle: f32,
+    velocity: f32,
+    pub throttle: f32,
+    pub brake: f32,
+    pub steering_command: f32,
+    steering_angle: f32,
+    steering_actuator: SteeringActuator,
     max_angle: f32,
     b: f32,
     a: f32,
     traveled_distance: f32,
-    goal_direction: f32,
+    model: VehicleModel,
+    powertrain: Powertrain,
+    mass: f32,
+    lateral_velocity: f32,
+    yaw_rate: f32,
+    longitudinal_acceleration: f32,
+    lateral_acceleration: f32,
 }
 
 impl Car {
     pub fn new(x:f32, y:f32, direction: f32) -> Car {
+        Car::from_params(x, y, direction, &VehicleParams::default())
+    }
+
+    pub fn from_params(x:f32, y:f32, direction: f32, params: &VehicleParams) -> Car {
         Car {
             pos_x: x,
             pos_y: y,
-            direction: direction,
-            view_angle: 160.0,
-            view_range: 20.0,
-            velocity: 0.0,
-            steering_angle: 0.0,         // -1.0 < angle < 1.0
-            max_angle: std::f32::consts::PI/8.0,        // angle @ steering_angle 1.0 / -1.0
-            b: 2.0,                         // distance between left anf right wheel
-            a: 2.0,                          // distance between front and rear axis
+            direction,
+            velocity: 0.0,                  // m/s
+            throttle: 0.0,                  // 0.0 < throttle < 1.0
+            brake: 0.0,                     // 0.0 < brake < 1.0
+            steering_command: 0.0,          // -1.0 < command < 1.0, saturated by the actuator
+            steering_angle: 0.0,            // -1.0 < angle < 1.0, follows the command with the actuator dynamics
+            steering_actuator: params.steering,
+            max_angle: params.max_steering_angle,       // angle @ steering_angle 1.0 / -1.0
+            b: params.track_width,          // distance between left anf right wheel
+            a: params.wheelbase,            // distance between front and rear axis
             traveled_distance: 0.0,
-            goal_direction: 0.0,
+            model: params.model,
+            powertrain: params.powertrain,
+            mass: params.mass,              // kg
+            lateral_velocity: 0.0,          // m/s at the center of gravity
+            yaw_rate: 0.0,                  // rad/s
+            longitudinal_acceleration: 0.0, // m/s^2
+            lateral_acceleration: 0.0,      // m/s^2 at the center of gravity
         }
     }
 }
 
 impl Car {
-    pub fn update_car_position (&mut self) {
+    /// Advances the car by `dt` seconds. `steering_command` moves the steering
+    /// angle through the actuator and `throttle` and `brake` change the
+    /// velocity in m/s, the pose is then integrated over `dt` so the result
+    /// does not depend on the frame rate. A `dt` of 0.0 or less leaves the car
+    /// as it is.
+    pub fn update_car_position (&mut self, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        self.steering_angle = self.steering_actuator.update(self.steering_angle, self.steering_command, dt);
+        let acceleration = self.powertrain.acceleration(self.throttle, self.brake, self.velocity, self.mass);
+        let old_velocity = self.velocity;
+        let old_lateral_velocity = self.lateral_velocity;
+        self.velocity = (self.velocity + acceleration*dt).max(0.0);
+        match self.model {
+            VehicleModel::Dynamic(params) if self.velocity >= DYNAMIC_MIN_VELOCITY => self.update_dynamic(params, dt),
+            VehicleModel::Dynamic(params) => {
+                self.update_kinematic(dt);
+                self.lateral_velocity = self.yaw_rate*params.cg_to_rear;    // rear axle does not slip
+            }
+            VehicleModel::Kinematic => self.update_kinematic(dt),
+        }
+        // what an accelerometer fixed to the car measures, including the centripetal part
+        self.longitudinal_acceleration = (self.velocity-old_velocity)/dt - self.lateral_velocity*self.yaw_rate;
+        self.lateral_acceleration = (self.lateral_velocity-old_lateral_velocity)/dt + self.velocity*self.yaw_rate;
+    }
+
+    fn update_kinematic(&mut self, dt: f32) {
         let old_x: f32 = self.pos_x;
         let old_y: f32 = self.pos_y;
+        let distance = self.velocity*dt;
         if self.steering_angle.abs() > 0.01 {
             let r = (self.steering_angle*self.max_angle+std::f32::consts::PI/2.0).tan()*self.a;    // radius of curve or between car and icc
-            let alpha = self.velocity/(r+self.b/2.0);                                    // angle of traveled distance per tick
+            let alpha = distance/(r+self.b/2.0);                                                  // angle of the arc traveled during dt
 
-            let len = ((alpha/2.0).tan()*(r+self.b/2.0))*2.0;     // eukled dist between actual and next position
-            let beta = self.direction + alpha;                    // angle to next position
+            let len = (alpha/2.0).sin()*(r+self.b/2.0)*2.0;      // chord length between actual and next position
+            let beta = self.direction + alpha/2.0;                // direction of the chord
 
             // update position
             self.pos_x += beta.cos()*len;
             self.pos_y += beta.sin()*len;
             self.direction += alpha;
-            /*if self.direction < 0.0 {
-                self.direction += std::f32::consts::PI*2.0;
-            } else if self.direction > std::f32::consts::PI*2.0 {
-                self.direction -= std::f32::consts::PI*2.0;
-            }*/
+            self.yaw_rate = alpha/dt;
 
         } else {    // straight movement
-            self.pos_x += self.direction.cos()*self.velocity;
-            self.pos_y += self.direction.sin()*self.velocity;
+            self.pos_x += self.direction.cos()*distance;
+            self.pos_y += self.direction.sin()*distance;
+            self.yaw_rate = 0.0;
         }
+        self.lateral_velocity = 0.0;
         self.traveled_distance += f32::sqrt((old_x-self.pos_x).powf(2.0)+(old_y-self.pos_y).powf(2.0));
     }
 
-    pub fn get_position(self) -> (f32,f32,f32) {
-        return (self.pos_x, self.pos_y, self.direction);
-    }
-
-    pub fn scan_cones(&mut self, blue_cones: Vec<(f64,f64)>, yellow_cones: Vec<(f64,f64)>) -> (Vec<(f64,f64)>,Vec<(f64,f64)>) {
-        let mut detected_blue: Vec<(f64,f64)> = vec![];
-        let mut detected_yellow: Vec<(f64,f64)> = vec![];
-        //println!("{}", self.direction);
-        for blue in &blue_cones {
-            if self.is_cone_in_range((blue.0 as f32, blue.1 as f32)) {
-                detected_blue.push(*blue);
-            } 
-        }
-        for yellow in yellow_cones {
-            if self.is_cone_in_range((yellow.0 as f32, yellow.1 as f32)) {
-                detected_yellow.push(yellow);
-            } 
-        }
-        let farest_cone = detected_blue.iter().max_by(|(a_x,a_y), (b_x,b_y)| self.euklid_dist_to_car(*a_x, *a_y).total_cmp(&self.euklid_dist_to_car(*b_x, *b_y)));
-        if farest_cone != None {
-            self.goal_direction = f32::atan2(farest_cone.unwrap().1 as f32 -self.pos_y, farest_cone.unwrap().0 as f32 -self.pos_x);
+    /// Single-track model with lateral tire forces from slip angles. The
+    /// longitudinal velocity is held at `velocity` during the substeps.
+    fn update_dynamic(&mut self, params: BicycleParams, dt: f32) {
+        let old_x: f32 = self.pos_x;
+        let old_y: f32 = self.pos_y;
+        let lr = params.cg_to_rear;
+        let lf = self.a - lr;
+        let delta = -self.steering_angle*self.max_angle;      // positive steering turns towards decreasing direction, like the kinematic model
+        let front_load = self.mass*GRAVITY*lr/self.a;
+        let rear_load = self.mass*GRAVITY*lf/self.a;
+        let vx = self.velocity;
+
+        let n_substeps = (dt/MAX_SUBSTEP).ceil().max(1.0) as u32;
+        let h = dt/(n_substeps as f32);
+        for _ in 0..n_substeps {
+            let front_slip = delta - f32::atan2(self.lateral_velocity + lf*self.yaw_rate, vx);
+            let rear_slip = -f32::atan2(self.lateral_velocity - lr*self.yaw_rate, vx);
+            let front_force = params.front_tire.lateral_force(front_slip, front_load);
+            let rear_force = params.rear_tire.lateral_force(rear_slip, rear_load);
+
+            // semi-implicit euler: velocities first, then the pose with the new velocities
+            self.lateral_velocity += ((front_force*delta.cos() + rear_force)/self.mass - vx*self.yaw_rate)*h;
+            self.yaw_rate += ((lf*front_force*delta.cos() - lr*rear_force)/params.yaw_inertia)*h;
+
+            let rear_lateral_velocity = self.lateral_velocity - lr*self.yaw_rate;   // the car position is the rear axle
+            self.pos_x += (vx*self.direction.cos() - rear_lateral_velocity*self.direction.sin())*h;
+            self.pos_y += (vx*self.direction.sin() + rear_lateral_velocity*self.direction.cos())*h;
+            self.direction += self.yaw_rate*h;
         }
-        return (detected_blue,detected_yellow)
+        self.traveled_distance += f32::sqrt((old_x-self.pos_x).powf(2.0)+(old_y-self.pos_y).powf(2.0));
     }
 
-    fn euklid_dist_to_car(self, x: f64, y: f64) -> f64 {
-        let x_diff = self.pos_x - x as f32;
-        let y_diff = self.pos_y - y as f32;
-        return f32::sqrt(f32::powf(x_diff,2.0) + f32::powf(y_diff,2.0)) as f64;
+    pub fn get_position(self) -> (f32,f32,f32) {
+        (self.pos_x, self.pos_y, self.direction)
     }
 
-    fn is_cone_in_range(self, cone: (f32,f32)) -> bool {
-        let x_diff = self.pos_x-cone.0;
-        let y_diff = self.pos_y-cone.1;
-        let distance = f32::sqrt(f32::powf(x_diff,2.0) + f32::powf(y_diff,2.0));
-        if distance <= self.view_range {
-            return self.is_cone_in_angle(y_diff,x_diff);
+    /// Rectangle between the rear and the front axis, as wide as the track width.
+    pub fn get_footprint(self) -> Footprint {
+        Footprint {
+            center: (self.pos_x + self.direction.cos()*self.a/2.0, self.pos_y + self.direction.sin()*self.a/2.0),
+            direction: self.direction,
+            half_length: self.a/2.0,
+            half_width: self.b/2.0,
         }
-        return false;
     }
 
-    fn is_cone_in_angle(self, y_diff: f32, x_diff:f32) -> bool {
-        let radiant = y_diff.atan2(x_diff) + std::f32::consts::PI*2.0;
-        let car_direction = self.direction % (std::f32::consts::PI*2.0);
-        let min_rad = car_direction - ((self.view_angle*std::f32::consts::PI)/180.0)/2.0 + std::f32::consts::PI;
-        let max_rad = car_direction + ((self.view_angle*std::f32::consts::PI)/180.0)/2.0 + std::f32::consts::PI;
-        if (min_rad < radiant && radiant < max_rad) ||
-         (min_rad < (radiant - std::f32::consts::PI*2.0) &&  (radiant - std::f32::consts::PI*2.0) < max_rad) || 
-         (min_rad < (radiant + std::f32::consts::PI*2.0) &&  (radiant + std::f32::consts::PI*2.0) < max_rad)  {
-            return true;
-        } 
-        return false;
+    /// World position of a detection, computed from the true pose of the car.
+    /// Only for ground truth and rendering, not for controllers.
+    pub fn detection_to_world(self, detection: Detection) -> Cone {
+        self.vehicle_to_world(detection.get_local_position())
     }
 
-    pub fn get_view_edge(self) -> Vec<(f64,f64)> {
-        let n = 20;
-        let mut points: Vec<(f64,f64)> = vec![];
-        let min_rad = self.direction - ((self.view_angle*std::f32::consts::PI)/180.0)/2.0;
-        let max_rad = self.direction + ((self.view_angle*std::f32::consts::PI)/180.0)/2.0;
-        for i in 0..n {
-            let d = (self.view_range /(n as f32))*(i as f32);
-            let min_x = (min_rad.cos()*d + self.pos_x) as f64;
-            let min_y = (min_rad.sin()*d + self.pos_y) as f64;
-            let max_x = (max_rad.cos()*d + self.pos_x) as f64;
-            let max_y = (max_rad.sin()*d + self.pos_y) as f64;
-
-            points.push((min_x,min_y));
-            points.push((max_x,max_y));
-            let angle_dx = (((self.view_angle*std::f32::consts::PI)/180.0)) /(n as f32);
-            let g = angle_dx*(i as f32);
-            let grad_x = ((min_rad + g).cos()*self.view_range + self.pos_x) as f64;
-            let grad_y = ((min_rad + g).sin()*self.view_range + self.pos_y) as f64;
-            points.push((grad_x,grad_y));
+    /// World position of a point in the vehicle frame, computed from the true
+    /// pose of the car. Only for ground truth and rendering.
+    pub fn vehicle_to_world(self, point: (f32,f32)) -> Cone {
+        let (sin, cos) = self.direction.sin_cos();
+        ((self.pos_x + point.0*cos - point.1*sin) as f64, (self.pos_y + point.0*sin + point.1*cos) as f64)
+    }
 
-        }
-        return points;
-    
+    /// The car `seconds` later on its current velocities, negative to look
+    /// back at samples taken during the last step. Only the pose moves.
+    pub fn extrapolate(self, seconds: f32) -> Car {
+        let rear_lateral_velocity = match self.model {
+            VehicleModel::Dynamic(params) => self.lateral_velocity - params.cg_to_rear*self.yaw_rate,
+            VehicleModel::Kinematic => 0.0,
+        };
+        let direction = self.direction + self.yaw_rate*seconds/2.0;    // mean heading on the way
+        let mut car = self;
+        car.pos_x += (self.velocity*direction.cos() - rear_lateral_velocity*direction.sin())*seconds;
+        car.pos_y += (self.velocity*direction.sin() + rear_lateral_velocity*direction.cos())*seconds;
+        car.direction += self.yaw_rate*seconds;
+        car
     }
+
      pub fn get_traveled_distance(self) -> f32 {
-        return self.traveled_distance;
+        self.traveled_distance
      }
 
-     pub fn get_goal_direction(self) -> f32 {
-        return self.goal_direction;
+     /// Applied normalized steering angle.
+     pub fn get_steering_angle(self) -> f32 {
+        self.steering_angle
+     }
 
+     /// Longitudinal velocity in m/s.
+     pub fn get_velocity(self) -> f32 {
+        self.velocity
      }
-}
 
+     pub fn get_yaw_rate(self) -> f32 {
+        self.yaw_rate
+     }
 
+     /// Lateral velocity of the center of gravity in the car frame.
+     pub fn get_lateral_velocity(self) -> f32 {
+        self.lateral_velocity
+     }
+
+     /// Acceleration of the car along its heading in m/s^2.
+     pub fn get_longitudinal_acceleration(self) -> f32 {
+        self.longitudinal_acceleration
+     }
+
+     /// Acceleration of the car across its heading in m/s^2, positive towards
+     /// increasing direction.
+     pub fn get_lateral_acceleration(self) -> f32 {
+        self.lateral_acceleration
+     }
+
+     /// Speeds of the rear wheels in m/s, first on the side of increasing
+     /// direction, then on the other side.
+     pub fn get_rear_wheel_speeds(self) -> (f32,f32) {
+        (self.velocity - self.yaw_rate*self.b/2.0, self.velocity + self.yaw_rate*self.b/2.0)
+     }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// Simulation clock counting fixed physics ticks.
+///
+/// The simulated time only advances when `tick` is called, so episode length,
+/// reward warm-up and controller timing are independent of the wall clock.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct SimClock {
+    dt: f32,
+    ticks: u64,
+}
+
+impl SimClock {
+    pub fn new(dt: f32) -> SimClock {
+        SimClock {
+            dt,
+            ticks: 0,
+        }
+    }
+
+    /// Advances the clock by one step of `dt` seconds.
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    pub fn reset(&mut self) {
+        self.ticks = 0;
+    }
+
+    /// Length of one tick in seconds.
+    pub fn get_dt(self) -> f32 {
+        self.dt
+    }
+
+    pub fn get_ticks(self) -> u64 {
+        self.ticks
+    }
+
+    /// Simulated seconds since the last reset.
+    pub fn get_time(self) -> f32 {
+        (self.ticks as f64 * self.dt as f64) as f32
+    }
+}
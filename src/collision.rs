@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+use crate::track::{ConeId, Track, CONE_RADIUS};
+
+/// Oriented rectangle covered by a car.
+#[derive(Copy, Clone)]
+pub struct Footprint {
+    pub center: (f32,f32),
+    pub direction: f32,
+    pub half_length: f32,
+    pub half_width: f32,
+}
+
+impl Footprint {
+    /// True if the circle around `point` with `radius` overlaps the rectangle.
+    pub fn intersects_circle(self, point: (f32,f32), radius: f32) -> bool {
+        // point in the frame of the rectangle, then the closest point of the rectangle to it
+        let x_diff = point.0 - self.center.0;
+        let y_diff = point.1 - self.center.1;
+        let local_x = x_diff*self.direction.cos() + y_diff*self.direction.sin();
+        let local_y = -x_diff*self.direction.sin() + y_diff*self.direction.cos();
+        let closest_x = local_x.clamp(-self.half_length, self.half_length);
+        let closest_y = local_y.clamp(-self.half_width, self.half_width);
+        (local_x-closest_x).powf(2.0) + (local_y-closest_y).powf(2.0) <= radius*radius
+    }
+
+    /// Separating axis test of two oriented rectangles.
+    pub fn intersects(self, other: Footprint) -> bool {
+        let axes = [self.direction, self.direction + std::f32::consts::PI/2.0,
+                    other.direction, other.direction + std::f32::consts::PI/2.0];
+        axes.iter().all(|axis| {
+            let (axis_x, axis_y) = (axis.cos(), axis.sin());
+            let distance = ((other.center.0-self.center.0)*axis_x + (other.center.1-self.center.1)*axis_y).abs();
+            distance <= self.projected_radius(axis_x, axis_y) + other.projected_radius(axis_x, axis_y)
+        })
+    }
+
+    /// Half the extent of the rectangle along the unit axis.
+    fn projected_radius(self, axis_x: f32, axis_y: f32) -> f32 {
+        let along = self.direction.cos()*axis_x + self.direction.sin()*axis_y;
+        let across = -self.direction.sin()*axis_x + self.direction.cos()*axis_y;
+        self.half_length*along.abs() + self.half_width*across.abs()
+    }
+
+    /// Ids of all cones of `track` touching the footprint.
+    pub fn colliding_cones(self, track: &Track) -> impl Iterator<Item = ConeId> + '_ {
+        let reach = self.half_length.hypot(self.half_width) + CONE_RADIUS as f32;
+        track.query_cones(self.center, reach)
+            .filter(move |(_, cone)| self.intersects_circle((cone.0 as f32, cone.1 as f32), CONE_RADIUS as f32))
+            .map(|(id, _)| *id)
+    }
+}
+
+/// A cone that started touching a car.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ConeHit {
+    pub cone: ConeId,
+    pub time: f32,      // simulated seconds
+}
+
+/// Another car that started touching a car, identified by its index in the world.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct CarHit {
+    pub car: usize,
+    pub time: f32,      // simulated seconds
+}
@@ -0,0 +1,191 @@
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::car::Car;
+use crate::env::Observation;
+use crate::sensor::{get_mount_pose, get_sector_outline, sensor_to_vehicle, ConeNoise, DelayLine, Detection, SampleTimer, Sensor};
+use crate::track::{Cone, ConeColor, Track, CONE_RADIUS};
+
+/// Mounting, field of view and errors of a detector that reports range and
+/// bearing of the blue and yellow cones.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConeSensorParams {
+    pub mount_x: f32,           // m in front of the rear axle
+    pub mount_y: f32,           // m towards increasing direction
+    pub mount_yaw: f32,         // rad, relative to the heading of the car
+    pub view_angle: f32,        // degree, centered on the mounting yaw
+    pub view_range: f32,        // m
+    pub noise: ConeNoise,
+    pub occlusion: bool,        // nearer cones hide the ones behind them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<f32>,   // Hz, None scans in every step
+    pub latency: f32,           // s until a scan is delivered
+}
+
+impl Default for ConeSensorParams {
+    fn default() -> ConeSensorParams {
+        ConeSensorParams {
+            mount_x: 0.0,
+            mount_y: 0.0,
+            mount_yaw: 0.0,
+            view_angle: 160.0,
+            view_range: 20.0,
+            noise: ConeNoise::default(),
+            occlusion: true,
+            sample_rate: None,
+            latency: 0.0,
+        }
+    }
+}
+
+/// Cones seen in one scan, in the vehicle frame.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ConeScan {
+    pub blue_cones: Vec<Detection>,
+    pub yellow_cones: Vec<Detection>,
+}
+
+/// Cone detector that scans at its own rate and delivers each scan after a
+/// latency. Observations hold the newest scan delivered so far. Scans that are
+/// replaced keep their buffers for the next scan, so scanning does not
+/// allocate once the buffers are large enough.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ConeSensor {
+    params: ConeSensorParams,
+    timer: SampleTimer,
+    delay: DelayLine<ConeScan>,
+    latest: ConeScan,
+    #[serde(skip)]
+    spare_scans: Vec<ConeScan>,
+    #[serde(skip)]
+    occluders: Vec<(f32,f32)>,  // range and world angle of the cones that can hide others
+}
+
+impl ConeSensor {
+    pub fn new(params: ConeSensorParams) -> ConeSensor {
+        ConeSensor {
+            params,
+            timer: params.sample_rate.map_or(SampleTimer::every_step(), SampleTimer::new),
+            delay: DelayLine::new(params.latency),
+            latest: ConeScan::default(),
+            spare_scans: vec![],
+            occluders: vec![],
+        }
+    }
+
+    pub fn get_params(&self) -> ConeSensorParams {
+        self.params
+    }
+
+    /// Cones inside the field of view as the sensor reports them in the
+    /// vehicle frame in a scan taken at `time`, with the errors configured in
+    /// `noise`, written to `detected_blue` and `detected_yellow`. False
+    /// positives are blue or yellow with equal probability. With `occlusion`
+    /// cones hidden behind nearer cones of any color, orange ones too, are not
+    /// seen.
+    pub fn scan<R: Rng>(&mut self, car: Car, track: &Track, time: f32, rng: &mut R, detected_blue: &mut Vec<Detection>, detected_yellow: &mut Vec<Detection>) {
+        let params = self.params;
+        let pose = get_mount_pose(self.get_mount(), car);
+        self.occluders.clear();
+        if params.occlusion {
+            self.occluders.extend(track.query_cones((pose.0, pose.1), params.view_range)
+                .map(|(_, cone)| get_polar(pose, *cone)));
+        }
+        detected_blue.clear();
+        detected_yellow.clear();
+        for (id, cone) in track.query_sector((pose.0, pose.1), pose.2, params.view_angle.to_radians(), params.view_range) {
+            match id.color {
+                ConeColor::Blue => self.detect_cone(pose, *cone, time, rng, detected_blue),
+                ConeColor::Yellow => self.detect_cone(pose, *cone, time, rng, detected_yellow),
+                ConeColor::Orange => (),
+            }
+        }
+        for _ in 0..params.noise.sample_false_positive_count(rng) {
+            let range = params.view_range*rng.gen::<f32>().sqrt();    // uniform over the area of the sector
+            let bearing = (rng.gen::<f32>()-0.5)*params.view_angle.to_radians();
+            let detection = self.to_vehicle_frame(Detection::new(time, range, bearing));
+            if rng.gen_bool(0.5) {
+                detected_blue.push(detection);
+            } else {
+                detected_yellow.push(detection);
+            }
+        }
+    }
+
+    /// Adds the detections of a cone in the field of view to `detected`,
+    /// `pose` is the world pose of the sensor.
+    fn detect_cone<R: Rng>(&self, pose: (f32,f32,f32), cone: Cone, time: f32, rng: &mut R, detected: &mut Vec<Detection>) {
+        let params = self.params;
+        let (range, angle) = get_polar(pose, cone);
+        if is_occluded(range, angle, &self.occluders) {
+            return;
+        }
+        if rng.gen::<f32>() >= params.noise.get_detection_probability(range, params.view_range) {
+            return;
+        }
+        let bearing = angle - pose.2;
+        let n_detections = if rng.gen::<f32>() < params.noise.duplicate_probability { 2 } else { 1 };
+        for _ in 0..n_detections {
+            detected.push(self.to_vehicle_frame(params.noise.measure(time, range, bearing, rng)));
+        }
+    }
+
+    /// Moves a detection from the sensor frame to the vehicle frame.
+    fn to_vehicle_frame(&self, detection: Detection) -> Detection {
+        let (x, y) = sensor_to_vehicle(self.get_mount(), detection.range, detection.bearing);
+        Detection::new(detection.time, x.hypot(y), y.atan2(x))
+    }
+}
+
+impl Sensor for ConeSensor {
+    fn get_mount(&self) -> (f32,f32,f32) {
+        (self.params.mount_x, self.params.mount_y, self.params.mount_yaw)
+    }
+
+    fn get_field_of_view(&self) -> Vec<(f32,f32)> {
+        get_sector_outline(self.get_mount(), self.params.view_angle.to_radians(), self.params.view_range)
+    }
+
+    fn reset(&mut self) {
+        *self = ConeSensor::new(self.params);
+    }
+
+    fn update(&mut self, car: Car, track: &Track, time: f32, dt: f32, rng: &mut ChaCha8Rng) {
+        if let Some(offset) = self.timer.advance(dt).last() {
+            let mut scan = self.spare_scans.pop().unwrap_or_default();
+            let scan_time = time - dt + offset;
+            self.scan(car.extrapolate(offset - dt), track, scan_time, rng, &mut scan.blue_cones, &mut scan.yellow_cones);
+            self.delay.push(scan_time, scan);
+        }
+        while let Some(scan) = self.delay.pop(time) {
+            let replaced = std::mem::replace(&mut self.latest, scan);
+            self.spare_scans.push(replaced);
+        }
+    }
+
+    /// Adds the newest scan, older than the observation with latency.
+    fn observe(&mut self, observation: &mut Observation) {
+        observation.blue_cones.extend_from_slice(&self.latest.blue_cones);
+        observation.yellow_cones.extend_from_slice(&self.latest.yellow_cones);
+    }
+}
+
+/// Distance and world angle from the sensor at `pose` to `cone`.
+fn get_polar(pose: (f32,f32,f32), cone: Cone) -> (f32,f32) {
+    let x_diff = cone.0 as f32 - pose.0;
+    let y_diff = cone.1 as f32 - pose.1;
+    (x_diff.hypot(y_diff), y_diff.atan2(x_diff))
+}
+
+/// A cone is hidden when the line of sight to its center passes through a
+/// nearer cone.
+fn is_occluded(range: f32, angle: f32, occluders: &[(f32,f32)]) -> bool {
+    let radius = CONE_RADIUS as f32;
+    occluders.iter().any(|(occluder_range, occluder_angle)| {
+        let difference = angle - occluder_angle;
+        *occluder_range < range && *occluder_range > radius
+            && difference.sin().atan2(difference.cos()).abs() < (radius/occluder_range).asin()
+    })
+}
@@ -1,95 +1,112 @@
+use serde::{Deserialize, Serialize};
 
-use std::iter::zip;
-
-const BASE_REWARD: f32 = 100.0;
-
-#[derive(Clone)]
+/// Number of samples of the phase between 0.0 and 1.0 in a trajectory.
+const N_SAMPLES: usize = 1000;
 
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "DmpWeights", into = "DmpWeights")]
 pub struct DMP {
     n: i32,
-    reward_trajectory: Vec<(f64,f64,f32)>,
-    variance: f32,
     weights: Vec<f32>,
-    last_reward: f32,
+    activations: Vec<f32>,  // of every base function at every sample, normalized to sum up to 1.0 per sample
 }
 
-impl DMP {
-    pub fn new(number_gaussians: i32, yellow_cones: Vec<(f64,f64)>, blue_cones: Vec<(f64,f64)>) -> DMP {
-        let mut r_trajectory: Vec<(f64,f64,f32)> = vec![];
-        let mut i: i32 = 0;
-        for (b_cone, y_cone) in zip(yellow_cones,blue_cones) {
-            let x = (b_cone.0 + y_cone.0)/2.0;
-            let y = (b_cone.1 + y_cone.1)/2.0;
-            let reward: f32 = i as f32 + BASE_REWARD;
-            r_trajectory.push((x,y,reward));
-            i += 1;
-        }
-        let weight_vec: Vec<f32> = vec![0.0;number_gaussians as usize];
-        DMP{
-            n: number_gaussians,
-            reward_trajectory: r_trajectory,
-            variance: 1.0,
-            weights: weight_vec,
-            last_reward: 0.0,
+/// What a DMP is saved as, the activations are recomputed when loading. One
+/// weight per base function is required.
+#[derive(Clone, Serialize, Deserialize)]
+struct DmpWeights {
+    n: i32,
+    weights: Vec<f32>,
+}
+
+impl TryFrom<DmpWeights> for DMP {
+    type Error = String;
+
+    fn try_from(saved: DmpWeights) -> Result<DMP, String> {
+        let mut dmp = DMP::new(saved.n);
+        if saved.weights.len() != dmp.weights.len() {
+            return Err(format!("expected {} dmp weights, found {}", dmp.weights.len(), saved.weights.len()));
         }
+        dmp.weights = saved.weights;
+        Ok(dmp)
     }
+}
 
-    pub fn get_reward(mut self, pos: (f32,f32) ) -> f32 {
-        let mut reward: f32 = 0.0;
-        for traj in self.reward_trajectory.iter() {
-            let distance = ((pos.0 - traj.0 as f32).powf(2.0) + (pos.1 - traj.1 as f32).powf(2.0)).sqrt();
-            if distance < 4.0 {
-                reward += (traj.2 / (distance* distance+ 2.0))*0.01;
-            }
-        }
-        if self.last_reward.abs() < 0.01 {
-            reward = reward + self.last_reward;
+impl From<DMP> for DmpWeights {
+    fn from(dmp: DMP) -> DmpWeights {
+        DmpWeights {
+            n: dmp.n,
+            weights: dmp.weights,
         }
-        self.last_reward = reward;
-        return reward;
     }
+}
 
-    fn base_function(self,x: f32, c:f32) -> f32 {
-        // variance = n_base_functions / c
-        return (f32::powf(x-c,2.0)* -(self.n as f32 / c)).exp();
+impl DMP {
+    pub fn new(number_gaussians: i32) -> DMP {
+        let weight_vec: Vec<f32> = vec![0.0;number_gaussians.max(0) as usize];
+        let mut dmp = DMP{
+            n: number_gaussians,
+            weights: weight_vec,
+            activations: vec![],
+        };
+        dmp.activations = dmp.compute_activations();
+        dmp
     }
 
-    pub fn set_weights(&mut self,  weights: Vec<f32>) {
-        if weights.len() == self.weights.len() {
-            self.weights = weights;
-        }
+    fn base_function(&self,x: f32, c:f32) -> f32 {
+        // variance = n_base_functions / c
+        (f32::powf(x-c,2.0)* -(self.n as f32 / c)).exp()
     }
 
-    fn forcing_function(self, goal: f32, state: f32) -> Vec<f32> {
-        let mut y_vec: Vec<f32> = vec![];
-        for i in 0..1000 {
+    /// Activations of the base functions at every sample of the phase, each
+    /// divided by their sum at that sample.
+    fn compute_activations(&self) -> Vec<f32> {
+        let n_base_functions = self.n.max(0);
+        let c = 1.0/(n_base_functions as f32);
+        let mut activations = Vec::with_capacity(N_SAMPLES*n_base_functions as usize);
+        for i in 0..N_SAMPLES {
             let x = i as f32 *0.001;
-            let mut base_sum = 0.0;
-            let mut base_sum_weights = 0.0;
-            let n_base_functions = self.n.clone();
+            let row = activations.len();
             for n in 0..n_base_functions {
-                let c = 1.0/(n_base_functions as f32);
-                let t = self.clone().base_function(x, c*((n+1) as f32));
-                base_sum_weights += t*self.weights[n as usize];
-                base_sum += t; //*x*(goal-state);
+                activations.push(self.base_function(x, c*((n+1) as f32)));
             }
+            let base_sum: f32 = activations[row..].iter().sum();
+            for t in activations[row..].iter_mut() {
+                *t /= base_sum;
+            }
+        }
+        activations
+    }
 
-            y_vec.push( (base_sum_weights/base_sum) * x * (goal-state));
+    pub fn set_weights(&mut self,  weights: Vec<f32>) {
+        if weights.len() == self.weights.len() {
+            self.weights = weights;
         }
-        return y_vec;
     }
 
-    pub fn generate_trajectory(self, goal: f32, state: f32) -> Vec<f32> {
-        return self.forcing_function(goal, state);
+    fn forcing_function(&self, goal: f32, state: f32) -> Vec<f32> {
+        if self.weights.is_empty() {
+            return vec![f32::NAN; N_SAMPLES];   // no base function to normalize by
+        }
+        self.activations.chunks_exact(self.weights.len()).enumerate()
+            .map(|(i, activations)| {
+                let x = i as f32 *0.001;
+                let base_sum_weights: f32 = activations.iter().zip(self.weights.iter()).map(|(t, w)| t*w).sum();
+                base_sum_weights * x * (goal-state)
+            })
+            .collect()
     }
 
-    
+    pub fn generate_trajectory(&self, goal: f32, state: f32) -> Vec<f32> {
+        self.forcing_function(goal, state)
+    }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PdController {
     kp: f32,
     kd: f32,
-    last_error: f32
+    last_error: Option<f32>,    // None before the first call
 
 }
 
@@ -99,15 +116,21 @@ impl PdController {
         PdController {
             kp: p,
             kd: d,
-            last_error: 0.0
+            last_error: None
         }
     }
 
-    pub fn compute(&mut self, state: f32, goal: f32) -> f32 {
+    /// `dt` is the simulated time in seconds since the last call, so the
+    /// derivative term is in error per second. There is no derivative term
+    /// on the first call and for a `dt` of 0.0 or less.
+    pub fn compute(&mut self, state: f32, goal: f32, dt: f32) -> f32 {
         let error = state-goal;
-        let error_diff = error - self.last_error;
-        self.last_error = error;
+        let error_diff = match self.last_error {
+            Some(last_error) if dt > 0.0 => (error - last_error)/dt,
+            _ => 0.0,
+        };
+        self.last_error = Some(error);
 
-        return self.kp * error + self.kd * error_diff;
+        self.kp * error + self.kd * error_diff
     }
 }
\ No newline at end of file
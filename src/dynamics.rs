@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+/// Vehicle model used by `Car::update_car_position`.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VehicleModel {
+    /// Wheels roll without slip, the car follows the steering arc exactly.
+    Kinematic,
+    /// Single-track model with tire slip, falls back to the kinematic model
+    /// below `DYNAMIC_MIN_VELOCITY`.
+    Dynamic(BicycleParams),
+}
+
+pub const GRAVITY: f32 = 9.81;
+pub const DYNAMIC_MIN_VELOCITY: f32 = 1.0;     // m/s, slip angles are ill-defined when standing still
+pub const MAX_SUBSTEP: f32 = 0.001;            // s, tire forces are stiff so the dynamic model is integrated in substeps
+
+/// Simplified Pacejka magic formula: F = D * Fz * sin(C * atan(B * slip_angle)).
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PacejkaTire {
+    pub b: f32,     // stiffness factor
+    pub c: f32,     // shape factor
+    pub d: f32,     // peak friction coefficient
+}
+
+impl PacejkaTire {
+    /// Lateral force in N for a slip angle in rad and a normal load in N.
+    pub fn lateral_force(self, slip_angle: f32, normal_load: f32) -> f32 {
+        self.d * normal_load * (self.c * (self.b * slip_angle).atan()).sin()
+    }
+}
+
+impl Default for PacejkaTire {
+    fn default() -> PacejkaTire {
+        PacejkaTire {
+            b: 10.0,
+            c: 1.9,
+            d: 1.5,
+        }
+    }
+}
+
+/// Parameters of the dynamic single-track model. The distance from the center
+/// of gravity to the front axle is the wheelbase of the car minus `cg_to_rear`.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BicycleParams {
+    pub yaw_inertia: f32,       // kg m^2
+    pub cg_to_rear: f32,        // m, center of gravity to rear axle
+    pub front_tire: PacejkaTire,
+    pub rear_tire: PacejkaTire,
+}
+
+impl Default for BicycleParams {
+    fn default() -> BicycleParams {
+        BicycleParams {
+            yaw_inertia: 120.0,
+            cg_to_rear: 0.9,
+            front_tire: PacejkaTire::default(),
+            rear_tire: PacejkaTire::default(),
+        }
+    }
+}
+
+/// Longitudinal forces from throttle and brake commands. Drag and rolling
+/// resistance always oppose the motion and the car does not drive backwards.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Powertrain {
+    pub max_drive_force: f32,       // N at full throttle
+    pub max_brake_force: f32,       // N at full brake
+    pub drag_coefficient: f32,      // N/(m/s)^2, 0.5 * air density * cd * frontal area
+    pub rolling_resistance: f32,    // fraction of the weight
+    pub max_acceleration: f32,      // m/s^2, traction limit
+    pub max_deceleration: f32,      // m/s^2, traction limit
+}
+
+impl Powertrain {
+    /// Longitudinal acceleration in m/s^2 for throttle and brake in [0, 1].
+    pub fn acceleration(self, throttle: f32, brake: f32, velocity: f32, mass: f32) -> f32 {
+        let drive_force = throttle.clamp(0.0, 1.0)*self.max_drive_force;
+        let resistance = brake.clamp(0.0, 1.0)*self.max_brake_force
+            + self.rolling_resistance*mass*GRAVITY
+            + self.drag_coefficient*velocity*velocity;
+        let mut force = drive_force - resistance;
+        if velocity <= 0.0 {
+            force = force.max(0.0);     // resistances only hold a standing car
+        }
+        (force/mass).clamp(-self.max_deceleration, self.max_acceleration)
+    }
+}
+
+impl Default for Powertrain {
+    fn default() -> Powertrain {
+        Powertrain {
+            max_drive_force: 3000.0,
+            max_brake_force: 5000.0,
+            drag_coefficient: 1.1,
+            rolling_resistance: 0.015,
+            max_acceleration: 10.0,
+            max_deceleration: 15.0,
+        }
+    }
+}
+
+/// Steering actuator between the commanded and the applied normalized
+/// steering angle: saturation at ±1, a first-order lag and a rate limit.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SteeringActuator {
+    pub max_rate: f32,          // 1/s, change of the normalized angle per second
+    pub time_constant: f32,     // s, 0.0 follows the command without lag
+}
+
+impl SteeringActuator {
+    /// Steering angle after `dt` seconds, starting at `angle` and driven by `command`.
+    pub fn update(self, angle: f32, command: f32, dt: f32) -> f32 {
+        let command = command.clamp(-1.0, 1.0);
+        let target = if self.time_constant > 0.0 {
+            angle + (command-angle)*(1.0 - (-dt/self.time_constant).exp())
+        } else {
+            command
+        };
+        let max_change = self.max_rate*dt;
+        (angle + (target-angle).clamp(-max_change, max_change)).clamp(-1.0, 1.0)
+    }
+}
+
+impl Default for SteeringActuator {
+    fn default() -> SteeringActuator {
+        SteeringActuator {
+            max_rate: 2.0,
+            time_constant: 0.05,
+        }
+    }
+}
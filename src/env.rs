@@ -0,0 +1,224 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::Agent;
+use crate::boundary::{BoundaryStatus, TrackBoundary};
+use crate::camera::BoundingBox;
+use crate::car::Car;
+use crate::clock::SimClock;
+use crate::collision::{CarHit, ConeHit};
+use crate::gnss::GnssReading;
+use crate::imu::ImuReading;
+use crate::lidar::LidarPoint;
+use crate::odometry::WheelSpeedReading;
+use crate::params::VehicleParams;
+use crate::reward::RewardFunction;
+use crate::snapshot::{EnvSnapshot, SnapshotError};
+use crate::timing::{LapTimer, TimingEvent};
+use crate::sensor::{Detection, Sensor};
+use crate::track::Track;
+
+/// Commands applied to the car for one step.
+#[derive(Copy, Clone, Default)]
+pub struct Action {
+    pub steering: f32,      // -1.0 < steering < 1.0, saturated by the car
+    pub throttle: f32,      // 0.0 < throttle < 1.0
+    pub brake: f32,         // 0.0 < brake < 1.0
+}
+
+/// Turns observations into actions, e.g. to drive a car in a `World`.
+/// Controllers with state across steps save it in `snapshot` and load it in
+/// `restore`.
+pub trait Controller {
+    fn act(&mut self, observation: &Observation, dt: f32) -> Action;
+
+    fn snapshot(&self) -> Result<serde_json::Value, serde_json::Error> {
+        Ok(serde_json::Value::Null)
+    }
+
+    /// A new controller in the state of `state`, `self` is left as it is.
+    /// `None` keeps using `self`, e.g. for controllers without state.
+    fn restore(&self, _state: &serde_json::Value) -> Result<Option<Box<dyn Controller>>, serde_json::Error> {
+        Ok(None)
+    }
+}
+
+/// What the car perceives after a step.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Observation {
+    pub blue_cones: Vec<Detection>,     // vehicle frame, newest scan of every cone sensor stamped with its time, the true pose is not observed
+    pub yellow_cones: Vec<Detection>,
+    pub goal_bearing: f32,      // rad from the heading to the farthest detected blue cone
+    pub velocity: f32,
+    pub steering_angle: f32,
+    pub point_cloud: Vec<LidarPoint>,   // LiDAR returns of the step in the vehicle frame, empty without a LiDAR
+    pub bounding_boxes: Vec<BoundingBox>,   // cones in the camera image, empty without a camera
+    pub imu: Vec<ImuReading>,               // samples of the step, empty without an IMU
+    pub wheel_speeds: Vec<WheelSpeedReading>,   // samples of the step, empty without wheel speed sensors
+    pub gnss: Vec<GnssReading>,             // fixes of the step, empty without a receiver or during an outage
+}
+
+impl Observation {
+    /// Empties all data but keeps the capacity of the buffers.
+    pub fn clear(&mut self) {
+        self.blue_cones.clear();
+        self.yellow_cones.clear();
+        self.goal_bearing = 0.0;
+        self.velocity = 0.0;
+        self.steering_angle = 0.0;
+        self.point_cloud.clear();
+        self.bounding_boxes.clear();
+        self.imu.clear();
+        self.wheel_speeds.clear();
+        self.gnss.clear();
+    }
+}
+
+/// Diagnostics of a step that are not part of the observation.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct StepInfo {
+    pub time: f32,
+    pub traveled_distance: f32,
+    pub cone_hits: Vec<ConeHit>,    // cones the car started touching in this step
+    pub car_hits: Vec<CarHit>,      // cars the car started touching in this step
+    pub boundary: BoundaryStatus,
+    pub timing: Option<TimingEvent>,  // timing line crossed in this step
+}
+
+/// Gym-style environment: a car on a generated track, driven by `step` and
+/// scored by a reward function.
+pub struct Env {
+    track: Track,
+    boundary: TrackBoundary,
+    agent: Agent,
+    observation: Observation,
+    clock: SimClock,
+    episode_length: f32,
+    end_on_off_track: bool,
+}
+
+impl Env {
+    /// `dt` is the physics step and `episode_length` the simulated seconds
+    /// after which an episode is done. The environment starts reset with seed 0.
+    pub fn new(dt: f32, episode_length: f32, reward: Box<dyn RewardFunction>) -> Env {
+        let track = Track::generate(&mut StdRng::seed_from_u64(0));
+        let boundary = TrackBoundary::new(&track);
+        let mut agent = Agent::new(VehicleParams::default(), reward, &track);
+        agent.reset(&track, &boundary, track.get_start_pose(), 0);
+        let mut observation = Observation::default();
+        agent.observe(&mut observation);
+        Env {
+            observation,
+            track,
+            boundary,
+            agent,
+            clock: SimClock::new(dt),
+            episode_length,
+            end_on_off_track: false,
+        }
+    }
+
+    /// Generates a new track from `seed` and places the car at the start. The
+    /// seed also drives the sensor noise.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.track = Track::generate(&mut StdRng::seed_from_u64(seed));
+        self.boundary = TrackBoundary::new(&self.track);
+        self.agent.reset(&self.track, &self.boundary, self.track.get_start_pose(), seed);
+        self.clock.reset();
+        self.agent.observe(&mut self.observation);
+        self.observation.clone()
+    }
+
+    /// Setup of the car, used from the next reset on.
+    pub fn set_vehicle_params(&mut self, vehicle_params: VehicleParams) {
+        self.agent.set_vehicle_params(vehicle_params);
+    }
+
+    /// Mounts a sensor in addition to the ones of the vehicle parameters, it
+    /// takes its first sample with the next reset.
+    pub fn add_sensor(&mut self, sensor: Box<dyn Sensor>) {
+        self.agent.add_sensor(sensor);
+    }
+
+    /// Subtracted from the reward for every cone hit.
+    pub fn set_cone_penalty(&mut self, cone_penalty: f32) {
+        self.agent.set_cone_penalty(cone_penalty);
+    }
+
+    /// Ends the episode as soon as the center of the car leaves the track.
+    pub fn set_end_on_off_track(&mut self, end_on_off_track: bool) {
+        self.end_on_off_track = end_on_off_track;
+    }
+
+    /// Observation, reward, whether the episode is done and the events of the
+    /// step. The observation is borrowed so stepping does not copy it.
+    pub fn step(&mut self, action: Action) -> (&Observation, f32, bool, StepInfo) {
+        let info = self.agent.drive(action, &self.track, &self.boundary, self.clock.get_time(), self.clock.get_dt());
+        self.clock.tick();
+        self.agent.observe(&mut self.observation);
+        let reward = self.agent.get_reward(&info);
+        (&self.observation, reward, self.is_done(), info)
+    }
+
+    /// Everything needed to continue the episode from this step with `restore`.
+    /// Fails if the state of the reward function or a sensor does not serialize.
+    pub fn snapshot(&self) -> Result<EnvSnapshot, SnapshotError> {
+        Ok(EnvSnapshot {
+            track: self.track.clone(),
+            clock: self.clock,
+            episode_length: self.episode_length,
+            end_on_off_track: self.end_on_off_track,
+            agent: self.agent.snapshot()?,
+            observation: self.observation.clone(),
+        })
+    }
+
+    /// Continues from a snapshot, the reward function has to be of the type
+    /// the snapshot was taken with. Nothing changes if the snapshot can not be
+    /// read.
+    pub fn restore(&mut self, snapshot: &EnvSnapshot) -> Result<Observation, SnapshotError> {
+        self.agent.restore(&snapshot.agent)?;
+        self.track = snapshot.track.clone();
+        self.boundary = TrackBoundary::new(&self.track);
+        self.clock = snapshot.clock;
+        self.episode_length = snapshot.episode_length;
+        self.end_on_off_track = snapshot.end_on_off_track;
+        self.observation = snapshot.observation.clone();
+        Ok(self.observation.clone())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.clock.get_time() >= self.episode_length || (self.end_on_off_track && !self.agent.get_boundary_status().on_track)
+    }
+
+    /// Ground truth state of the car, e.g. for rendering.
+    pub fn get_car(&self) -> Car {
+        self.agent.get_car()
+    }
+
+    pub fn get_track(&self) -> &Track {
+        &self.track
+    }
+
+    /// All cone hits since the last reset.
+    pub fn get_cone_hits(&self) -> &[ConeHit] {
+        self.agent.get_cone_hits()
+    }
+
+    pub fn get_lap_timer(&self) -> &LapTimer {
+        self.agent.get_lap_timer()
+    }
+
+    pub fn get_boundary(&self) -> &TrackBoundary {
+        &self.boundary
+    }
+
+    pub fn get_boundary_status(&self) -> BoundaryStatus {
+        self.agent.get_boundary_status()
+    }
+
+    pub fn get_clock(&self) -> SimClock {
+        self.clock
+    }
+}
@@ -0,0 +1,134 @@
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Exp};
+use serde::{Deserialize, Serialize};
+
+use crate::car::Car;
+use crate::env::Observation;
+use crate::sensor::{drift, gaussian, DelayLine, SampleTimer, Sensor};
+use crate::track::Track;
+
+/// Errors of a dual antenna GNSS receiver: white noise, a slowly wandering
+/// position offset, occasional multipath jumps and outages without fix. The
+/// outages are random, in configured time windows or both.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GnssParams {
+    pub sample_rate: f32,               // Hz
+    pub latency: f32,                   // s until a fix is delivered
+    pub position_std: f32,              // m
+    pub heading_std: f32,               // rad
+    pub position_drift: f32,            // m per sqrt(s), random walk of the position offset
+    pub multipath_probability: f32,     // of a sample with an additional multipath error
+    pub multipath_std: f32,             // m
+    pub outage_rate: f32,               // random outages per second, 0.0 for none
+    pub outage_duration: f32,           // s, mean length of a random outage
+    pub outage_windows: Vec<(f32,f32)>, // s since the start of the episode, from and until when there is no fix
+}
+
+impl Default for GnssParams {
+    fn default() -> GnssParams {
+        GnssParams {
+            sample_rate: 10.0,
+            latency: 0.0,
+            position_std: 0.3,
+            heading_std: 0.02,
+            position_drift: 0.05,
+            multipath_probability: 0.01,
+            multipath_std: 2.0,
+            outage_rate: 0.02,
+            outage_duration: 3.0,
+            outage_windows: vec![],
+        }
+    }
+}
+
+/// One fix in the world frame.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct GnssReading {
+    pub time: f32,          // s, when the fix was taken
+    pub x: f32,             // m
+    pub y: f32,             // m
+    pub heading: f32,       // rad
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Gnss {
+    params: GnssParams,
+    timer: SampleTimer,
+    delay: DelayLine<GnssReading>,
+    delivered: Vec<GnssReading>,    // since the last observation
+    offset: (f32,f32),          // m, slowly wandering position error
+    outage_remaining: f32,      // s until the receiver has a fix again after a random outage
+}
+
+impl Gnss {
+    pub fn new(params: GnssParams) -> Gnss {
+        Gnss {
+            timer: SampleTimer::new(params.sample_rate),
+            delay: DelayLine::new(params.latency),
+            params,
+            delivered: vec![],
+            offset: (0.0, 0.0),
+            outage_remaining: 0.0,
+        }
+    }
+
+    /// Whether a random outage is going on.
+    pub fn is_in_outage(&self) -> bool {
+        self.outage_remaining > 0.0
+    }
+
+    /// Whether `time` falls into one of the configured outage windows.
+    pub fn is_in_outage_window(&self, time: f32) -> bool {
+        self.params.outage_windows.iter().any(|(start, end)| (*start..*end).contains(&time))
+    }
+}
+
+impl Sensor for Gnss {
+    fn reset(&mut self) {
+        *self = Gnss::new(self.params.clone());
+    }
+
+    /// Advances the receiver by `dt` seconds and takes the fixes of the
+    /// samples due, none during a random outage or an outage window. Each fix
+    /// is taken where the car was when it was due.
+    fn update(&mut self, car: Car, _track: &Track, time: f32, dt: f32, rng: &mut ChaCha8Rng) {
+        let params = &self.params;
+        self.offset = (drift(self.offset.0, params.position_drift, dt, rng), drift(self.offset.1, params.position_drift, dt, rng));
+        if self.is_in_outage() {
+            self.outage_remaining -= dt;
+        } else if rng.gen::<f32>() < params.outage_rate*dt {
+            self.outage_remaining = match Exp::new(1.0/params.outage_duration) {
+                Ok(duration) => duration.sample(rng),
+                Err(_) => 0.0,  // duration is 0.0 or negative
+            };
+        }
+        let samples = self.timer.advance(dt);
+        let n_fixes = if self.is_in_outage() { 0 } else { samples.len() };
+
+        for offset in samples.take(n_fixes) {
+            let sample_time = time - dt + offset;
+            if self.is_in_outage_window(sample_time) {
+                continue;
+            }
+            let (x, y, heading) = car.extrapolate(offset - dt).get_position();
+            let (mut error_x, mut error_y) = (gaussian(params.position_std, rng), gaussian(params.position_std, rng));
+            if rng.gen::<f32>() < params.multipath_probability {
+                error_x += gaussian(params.multipath_std, rng);
+                error_y += gaussian(params.multipath_std, rng);
+            }
+            self.delay.push(sample_time, GnssReading {
+                time: sample_time,
+                x: x + self.offset.0 + error_x,
+                y: y + self.offset.1 + error_y,
+                heading: heading + gaussian(params.heading_std, rng),
+            });
+        }
+        self.delay.deliver(time, &mut self.delivered);
+    }
+
+    fn observe(&mut self, observation: &mut Observation) {
+        observation.gnss.append(&mut self.delivered);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::track::{Cone, ConeId};
+
+/// Uniform grid over the cones of a track. Queries only visit the cells
+/// around the query area, so their cost does not grow with the track.
+#[derive(Clone, Default)]
+pub struct ConeGrid {
+    origin: Cone,               // m, corner of the first cell
+    cell_size: f64,             // m
+    n_columns: usize,
+    n_rows: usize,
+    cell_starts: Vec<usize>,    // index of the first cone of every cell in `cones`, one more than cells
+    cones: Vec<(ConeId, Cone)>, // sorted by cell, rows of cells one after the other
+}
+
+impl ConeGrid {
+    pub fn new(cones: impl Iterator<Item = (ConeId, Cone)>, cell_size: f64) -> ConeGrid {
+        let cones: Vec<(ConeId, Cone)> = cones.collect();
+        if cones.is_empty() {
+            return ConeGrid::default();
+        }
+        let min_x = cones.iter().map(|(_, cone)| cone.0).fold(f64::INFINITY, f64::min);
+        let min_y = cones.iter().map(|(_, cone)| cone.1).fold(f64::INFINITY, f64::min);
+        let max_x = cones.iter().map(|(_, cone)| cone.0).fold(f64::NEG_INFINITY, f64::max);
+        let max_y = cones.iter().map(|(_, cone)| cone.1).fold(f64::NEG_INFINITY, f64::max);
+        let mut grid = ConeGrid {
+            origin: (min_x, min_y),
+            cell_size,
+            n_columns: ((max_x-min_x)/cell_size) as usize + 1,
+            n_rows: ((max_y-min_y)/cell_size) as usize + 1,
+            cell_starts: vec![],
+            cones: vec![],
+        };
+        let cells: Vec<usize> = cones.iter().map(|(_, cone)| grid.get_cell_index(*cone)).collect();
+        let mut counts = vec![0; grid.n_columns*grid.n_rows + 1];
+        for cell in cells.iter() {
+            counts[cell + 1] += 1;
+        }
+        for i in 1..counts.len() {
+            counts[i] += counts[i-1];
+        }
+        let mut cones: Vec<(usize, (ConeId, Cone))> = cells.into_iter().zip(cones).collect();
+        cones.sort_by_key(|(cell, _)| *cell);
+        grid.cell_starts = counts;
+        grid.cones = cones.into_iter().map(|(_, cone)| cone).collect();
+        grid
+    }
+
+    /// Cones at most `radius` m from `center`.
+    pub fn query(&self, center: Cone, radius: f64) -> impl Iterator<Item = &(ConeId, Cone)> + '_ {
+        let (min_column, min_row) = self.get_cell((center.0 - radius, center.1 - radius));
+        let (max_column, max_row) = self.get_cell((center.0 + radius, center.1 + radius));
+        let n_rows = if self.cones.is_empty() { 0 } else { max_row + 1 };
+        // the cells of a row that overlap the query are one slice of `cones`
+        (min_row..n_rows).flat_map(move |row| {
+            let start = self.cell_starts[row*self.n_columns + min_column];
+            let end = self.cell_starts[row*self.n_columns + max_column + 1];
+            self.cones[start..end].iter()
+        }).filter(move |(_, cone)| (cone.0 - center.0).powi(2) + (cone.1 - center.1).powi(2) <= radius*radius)
+    }
+
+    /// Column and row of the cell containing `point`, clamped to the grid.
+    fn get_cell(&self, point: Cone) -> (usize,usize) {
+        let column = ((point.0 - self.origin.0)/self.cell_size).floor().max(0.0) as usize;
+        let row = ((point.1 - self.origin.1)/self.cell_size).floor().max(0.0) as usize;
+        (column.min(self.n_columns.saturating_sub(1)), row.min(self.n_rows.saturating_sub(1)))
+    }
+
+    fn get_cell_index(&self, point: Cone) -> usize {
+        let (column, row) = self.get_cell(point);
+        row*self.n_columns + column
+    }
+}
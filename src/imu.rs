@@ -0,0 +1,97 @@
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::car::Car;
+use crate::env::Observation;
+use crate::sensor::{drift, gaussian, DelayLine, SampleTimer, Sensor};
+use crate::track::Track;
+
+/// Noise of a gyroscope around the vertical axis and a two axis
+/// accelerometer. Biases start at zero and drift as a random walk.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImuParams {
+    pub sample_rate: f32,               // Hz
+    pub latency: f32,                   // s until a sample is delivered
+    pub yaw_rate_std: f32,              // rad/s
+    pub acceleration_std: f32,          // m/s^2
+    pub yaw_rate_bias_drift: f32,       // rad/s per sqrt(s)
+    pub acceleration_bias_drift: f32,   // m/s^2 per sqrt(s)
+}
+
+impl Default for ImuParams {
+    fn default() -> ImuParams {
+        ImuParams {
+            sample_rate: 100.0,
+            latency: 0.0,
+            yaw_rate_std: 0.005,
+            acceleration_std: 0.05,
+            yaw_rate_bias_drift: 0.0005,
+            acceleration_bias_drift: 0.005,
+        }
+    }
+}
+
+/// One IMU sample in the car frame.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ImuReading {
+    pub time: f32,                      // s, when the sample was taken
+    pub yaw_rate: f32,                  // rad/s
+    pub longitudinal_acceleration: f32, // m/s^2
+    pub lateral_acceleration: f32,      // m/s^2, positive towards increasing direction
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Imu {
+    params: ImuParams,
+    timer: SampleTimer,
+    delay: DelayLine<ImuReading>,
+    delivered: Vec<ImuReading>,     // since the last observation
+    yaw_rate_bias: f32,
+    longitudinal_bias: f32,
+    lateral_bias: f32,
+}
+
+impl Imu {
+    pub fn new(params: ImuParams) -> Imu {
+        Imu {
+            params,
+            timer: SampleTimer::new(params.sample_rate),
+            delay: DelayLine::new(params.latency),
+            delivered: vec![],
+            yaw_rate_bias: 0.0,
+            longitudinal_bias: 0.0,
+            lateral_bias: 0.0,
+        }
+    }
+}
+
+impl Sensor for Imu {
+    fn reset(&mut self) {
+        *self = Imu::new(self.params);
+    }
+
+    /// Drifts the biases by `dt` seconds and samples the motion of the last
+    /// `update_car_position` as often as the sample rate asks for, each
+    /// reading stamped with the time it was due.
+    fn update(&mut self, car: Car, _track: &Track, time: f32, dt: f32, rng: &mut ChaCha8Rng) {
+        let params = self.params;
+        self.yaw_rate_bias = drift(self.yaw_rate_bias, params.yaw_rate_bias_drift, dt, rng);
+        self.longitudinal_bias = drift(self.longitudinal_bias, params.acceleration_bias_drift, dt, rng);
+        self.lateral_bias = drift(self.lateral_bias, params.acceleration_bias_drift, dt, rng);
+        for offset in self.timer.advance(dt) {
+            let sample_time = time - dt + offset;
+            self.delay.push(sample_time, ImuReading {
+                time: sample_time,
+                yaw_rate: car.get_yaw_rate() + self.yaw_rate_bias + gaussian(params.yaw_rate_std, rng),
+                longitudinal_acceleration: car.get_longitudinal_acceleration() + self.longitudinal_bias + gaussian(params.acceleration_std, rng),
+                lateral_acceleration: car.get_lateral_acceleration() + self.lateral_bias + gaussian(params.acceleration_std, rng),
+            });
+        }
+        self.delay.deliver(time, &mut self.delivered);
+    }
+
+    fn observe(&mut self, observation: &mut Observation) {
+        observation.imu.append(&mut self.delivered);
+    }
+}
@@ -0,0 +1,28 @@
+//! Simulation core of a formula student track: vehicle model, track
+//! generation, a gym-style environment, a multi-car world, DMP policy and
+//! plotting buffers. Rendering lives in the `car-simulation` binary, so this
+//! crate does not depend on macroquad.
+
+pub mod agent;
+pub mod boundary;
+pub mod camera;
+pub mod car;
+pub mod clock;
+pub mod collision;
+pub mod cone_sensor;
+pub mod dmp;
+pub mod dynamics;
+pub mod env;
+pub mod gnss;
+pub mod grid;
+pub mod imu;
+pub mod lidar;
+pub mod odometry;
+pub mod params;
+pub mod plot;
+pub mod reward;
+pub mod sensor;
+pub mod snapshot;
+pub mod timing;
+pub mod track;
+pub mod world;
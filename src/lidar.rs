@@ -0,0 +1,203 @@
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::car::Car;
+use crate::env::Observation;
+use crate::sensor::{gaussian, get_mount_pose, get_sector_outline, sensor_to_vehicle, DelayLine, Sensor};
+use crate::track::{Track, CONE_HEIGHT, CONE_RADIUS};
+
+/// Mounting and scan pattern of a rotating multi-channel LiDAR.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LidarParams {
+    pub mount_x: f32,               // m in front of the rear axle
+    pub mount_y: f32,               // m towards increasing direction
+    pub mount_height: f32,          // m above the ground
+    pub mount_yaw: f32,             // rad, relative to the heading of the car
+    pub channels: usize,            // beams stacked vertically
+    pub vertical_fov: f32,          // degree, spread evenly around the horizontal plane
+    pub horizontal_fov: f32,        // degree, centered on the mounting yaw
+    pub angular_resolution: f32,    // degree between two rays of a channel
+    pub rotation_rate: f32,         // revolutions per second
+    pub max_range: f32,             // m
+    pub range_std: f32,             // m, standard deviation of the measured range
+    pub latency: f32,               // s until a point is delivered
+}
+
+impl Default for LidarParams {
+    fn default() -> LidarParams {
+        LidarParams {
+            mount_x: 1.5,
+            mount_y: 0.0,
+            mount_height: 0.25,
+            mount_yaw: 0.0,
+            channels: 16,
+            vertical_fov: 30.0,
+            horizontal_fov: 360.0,
+            angular_resolution: 0.2,
+            rotation_rate: 10.0,
+            max_range: 30.0,
+            range_std: 0.02,
+            latency: 0.0,
+        }
+    }
+}
+
+/// Return of one ray in the vehicle frame: x forward from the rear axle, y
+/// towards increasing direction and z up from the ground.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct LidarPoint {
+    pub time: f32,          // s, when the ray was fired
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub channel: usize,
+}
+
+/// Rotating LiDAR that hits cone surfaces and the ground. Every step fires
+/// the rays the head sweeps over in that time, so a full point cloud builds
+/// up over one revolution.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Lidar {
+    params: LidarParams,
+    next_ray: usize,        // index of the next azimuth in the revolution
+    ray_budget: f32,        // azimuths swept but not yet fired
+    delay: DelayLine<LidarPoint>,
+    delivered: Vec<LidarPoint>,     // since the last observation
+    #[serde(skip)]
+    cones: Vec<(f32,f32)>,          // in reach of the rays, relative to the sensor
+}
+
+impl Lidar {
+    pub fn new(params: LidarParams) -> Lidar {
+        Lidar {
+            params,
+            next_ray: 0,
+            ray_budget: 0.0,
+            delay: DelayLine::new(params.latency),
+            delivered: vec![],
+            cones: vec![],
+        }
+    }
+
+    pub fn get_params(&self) -> LidarParams {
+        self.params
+    }
+
+    /// Rotates the head by `dt` seconds up to `time` and fires the rays swept
+    /// meanwhile, each at the time and from the pose it was swept at.
+    fn scan<R: Rng>(&mut self, car: Car, track: &Track, time: f32, dt: f32, rng: &mut R) {
+        let params = self.params;
+        let rays_per_revolution = ((360.0/params.angular_resolution).round() as usize).max(1);
+        let ray_rate = rays_per_revolution as f32*params.rotation_rate;   // rays per s
+        let previous_budget = self.ray_budget;
+        self.ray_budget += ray_rate*dt;
+        let n_rays = self.ray_budget.floor() as usize;
+        self.ray_budget -= n_rays as f32;
+
+        let (x, y, _) = get_mount_pose(self.get_mount(), car);
+        // only cones the longest ray can reach from anywhere on the way of this step are tested
+        let reach = params.max_range + CONE_RADIUS as f32 + car.get_velocity()*dt;
+        self.cones.clear();
+        self.cones.extend(track.query_cones((x, y), reach).map(|(_, cone)| (cone.0 as f32 - x, cone.1 as f32 - y)));
+
+        for ray in 0..n_rays {
+            // the budget reaches the next whole ray after this offset into the step
+            let offset = ((ray as f32 + 1.0 - previous_budget)/ray_rate).clamp(0.0, dt);
+            let ray_time = time - dt + offset;
+            let (ray_x, ray_y, yaw) = get_mount_pose(self.get_mount(), car.extrapolate(offset - dt));
+            let azimuth = (self.next_ray as f32/rays_per_revolution as f32)*std::f32::consts::PI*2.0;
+            self.next_ray = (self.next_ray + 1) % rays_per_revolution;
+            let azimuth = azimuth.sin().atan2(azimuth.cos());
+            if azimuth.abs() > params.horizontal_fov.to_radians()/2.0 {
+                continue;
+            }
+            let direction = ((yaw + azimuth).cos(), (yaw + azimuth).sin());
+            for channel in 0..params.channels {
+                let elevation = self.get_elevation(channel);
+                let Some(distance) = cast_ray((ray_x - x, ray_y - y), direction, elevation.tan(), params.mount_height, &self.cones) else {
+                    continue;
+                };
+                let range = distance/elevation.cos() + gaussian(params.range_std, rng);
+                if range > params.max_range || range <= 0.0 {
+                    continue;
+                }
+                let (point_x, point_y) = sensor_to_vehicle(self.get_mount(), range*elevation.cos(), azimuth);
+                self.delay.push(ray_time, LidarPoint {
+                    time: ray_time,
+                    x: point_x,
+                    y: point_y,
+                    z: params.mount_height + range*elevation.sin(),
+                    channel,
+                });
+            }
+        }
+        self.delay.deliver(time, &mut self.delivered);
+    }
+
+    /// Elevation of `channel` in rad, the channels spread evenly over the
+    /// vertical field of view from bottom to top.
+    fn get_elevation(&self, channel: usize) -> f32 {
+        let vertical_fov = self.params.vertical_fov.to_radians();
+        if self.params.channels > 1 {
+            -vertical_fov/2.0 + vertical_fov*(channel as f32)/((self.params.channels-1) as f32)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Sensor for Lidar {
+    fn get_mount(&self) -> (f32,f32,f32) {
+        (self.params.mount_x, self.params.mount_y, self.params.mount_yaw)
+    }
+
+    fn get_field_of_view(&self) -> Vec<(f32,f32)> {
+        get_sector_outline(self.get_mount(), self.params.horizontal_fov.to_radians(), self.params.max_range)
+    }
+
+    fn reset(&mut self) {
+        *self = Lidar::new(self.params);
+    }
+
+    fn update(&mut self, car: Car, track: &Track, time: f32, dt: f32, rng: &mut ChaCha8Rng) {
+        self.scan(car, track, time, dt, rng);
+    }
+
+    fn observe(&mut self, observation: &mut Observation) {
+        observation.point_cloud.append(&mut self.delivered);
+    }
+}
+
+/// Horizontal distance to the first hit of a ray starting `height` m above
+/// the ground at `origin` with a horizontal unit `direction` and a `slope` of
+/// rise per meter. Cones are upright cones given in the same frame as
+/// `origin`.
+fn cast_ray(origin: (f32,f32), direction: (f32,f32), slope: f32, height: f32, cones: &[(f32,f32)]) -> Option<f32> {
+    let radius = CONE_RADIUS as f32;
+    let cone_height = CONE_HEIGHT as f32;
+    let mut closest = if slope < 0.0 { Some(-height/slope) } else { None };   // ground
+    // radius of a cone at the height of the ray: a0 + a1*s for a horizontal distance s
+    let a0 = radius*(1.0 - height/cone_height);
+    let a1 = -radius*slope/cone_height;
+    for (cone_x, cone_y) in cones {
+        let (cone_x, cone_y) = (cone_x - origin.0, cone_y - origin.1);
+        let along = direction.0*cone_x + direction.1*cone_y;
+        let distance_squared = cone_x*cone_x + cone_y*cone_y;
+        // |s*direction - cone|^2 = (a0 + a1*s)^2
+        let a = 1.0 - a1*a1;
+        let b = -2.0*(along + a0*a1);
+        let c = distance_squared - a0*a0;
+        let discriminant = b*b - 4.0*a*c;
+        if a <= 0.0 || discriminant < 0.0 {
+            continue;
+        }
+        let s = (-b - discriminant.sqrt())/(2.0*a);
+        let z = height + slope*s;
+        if s > 0.0 && (0.0..=cone_height).contains(&z) && closest.is_none_or(|closest| s < closest) {
+            closest = Some(s);
+        }
+    }
+    closest
+}
@@ -1,21 +1,32 @@
+use std::cell::Cell;
 use std::iter::zip;
+use std::rc::Rc;
 use std::vec;
-use rand_distr::Normal;
-use rand::distributions::Uniform;
-use std::time::{Duration, SystemTime};
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
 
 use macroquad::prelude as mq;
 
-use rand::prelude::*;
-
 const METER_TO_PXL: f32 = 5.0;      // One meter has the size of 5 pixel
-
-use crate::car::Car;
-use crate::dmp::DMP;
-use crate::dmp::PdController;
-
-pub mod car;
-pub mod dmp;
+const TIME_STEP: f32 = 1.0/60.0;    // physics step in seconds
+const EPISODE_LENGTH: f32 = 10.0;   // simulated seconds per rollout
+const REWARD_WARMUP: f32 = 0.0005;  // simulated seconds before rewards are counted
+const CONE_PENALTY: f32 = 1.0;      // subtracted from the reward for every cone hit
+const N_BASE_FUNCTIONS: i32 = 40;
+const N_SAMPLES: usize = 15;        // rollouts per weight update
+const N_TOP_SAMPLES: usize = 5;     // best rollouts averaged into the new weights
+
+use car_simulation::car::Car;
+use car_simulation::dmp::DMP;
+use car_simulation::dmp::PdController;
+use car_simulation::dynamics::{BicycleParams, VehicleModel};
+use car_simulation::env::{Action, Controller, Observation};
+use car_simulation::params::VehicleParams;
+use car_simulation::plot::PlotData;
+use car_simulation::reward::TrackReward;
+use car_simulation::snapshot::save_state;
+use car_simulation::track::{ConeColor, CONE_RADIUS};
+use car_simulation::world::World;
 
 #[derive(Copy, Clone)]
 
@@ -28,11 +39,56 @@ struct Screen {
     m_pressed_y: f32
 }
 
-#[macroquad::main("BasicShapes")]
-async fn main() {
-
+/// Runs the training headless as fast as possible. Pass `--watch` to open a
+/// window and render every rollout instead, `--vehicle <file>` to load the
+/// vehicle parameters from a toml or json file and `--dynamic` to use the
+/// dynamic single-track model with default tire parameters if the file does
+/// not configure it.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut vehicle_params = VehicleParams::default();
+    if let Some(index) = args.iter().position(|arg| arg == "--vehicle") {
+        let Some(path) = args.get(index+1) else {
+            eprintln!("--vehicle needs a file path");
+            std::process::exit(1);
+        };
+        vehicle_params = match VehicleParams::from_file(path) {
+            Ok(params) => params,
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        };
+    }
+    if args.iter().any(|arg| arg == "--dynamic") && !matches!(vehicle_params.model, VehicleModel::Dynamic(_)) {
+        vehicle_params.model = VehicleModel::Dynamic(BicycleParams::default());
+        if let Err(error) = vehicle_params.validate() {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+    if args.iter().any(|arg| arg == "--watch") {
+        macroquad::Window::new("car-simulation", watch_training(vehicle_params));
+    } else {
+        train_headless(vehicle_params);
+    }
+}
 
+fn train_headless(vehicle_params: VehicleParams) {
+    let mut trainer = Trainer::new(N_BASE_FUNCTIONS);
+    loop {
+        let mut rollout = Rollout::new(&mut trainer, &vehicle_params);
+        while !rollout.is_done() {
+            rollout.step();
+        }
+        for reward in rollout.sum_rewards.iter() {
+            trainer.add_result(*reward);
+        }
+        trainer.update_weights();
+    }
+}
 
+async fn watch_training(vehicle_params: VehicleParams) {
     let mut display = Screen {
         x: mq::screen_width()/2.0,
         y: mq::screen_height()/2.0,
@@ -42,187 +98,272 @@ async fn main() {
         m_pressed_y: 0.0
     };
 
-    let mut init_flag = false; 
-
-    let n_base_functions = 40;
-    let mut rng = rand::thread_rng();
-    let range: Uniform<f32> = Uniform::new(-1.0, 1.0);
-    let mut weights: Vec<f32> = (0..n_base_functions).map(|_| rng.sample(&range)).collect();
-    weights = (0..n_base_functions).map(|_| 0.0).collect();
-
-    let n_samples = 15;
-    let mut sample_rewards: Vec<f32> = vec![];
-    let mut sample_weights: Vec<Vec<f32>> = vec![];
-    
-
-
-
+    let mut trainer = Trainer::new(N_BASE_FUNCTIONS);
+    let mut target_velocity = 6.0;  // m/s
     loop {
-
-        for i_samples in 0..n_samples {
-
-            
-
-            let mut plot_data: PlotData = PlotData::new(300);
-
-            let (blue_cones,yellow_cones,orange_cones) = generate_cone_lists();
-
-            display.x = (orange_cones[0].0 + orange_cones[1].0) as f32/2.0 - mq::screen_height()/2.0;
-            display.y = (orange_cones[0].1 + orange_cones[1].1) as f32/2.0;
-
-            let mut race_car = Car::new((orange_cones[0].0+orange_cones[1].0) as f32 /2.0, 
-                                                (orange_cones[0].1+orange_cones[1].1) as f32 /2.0,
-                                        3.0*std::f32::consts::PI/2.0);
-
-            let mut steering_controller = PdController::new(5.0,0.001);
-            let mut steering_goal: f32 = 0.0;
-            let mut dmp = DMP::new(n_base_functions, yellow_cones.clone(), blue_cones.clone());
-            
-            let weight_samples: Vec<f32> = weights.iter().map(|mean| Normal::new(*mean, 2.0).unwrap().sample( &mut rand::thread_rng())).collect();
-            sample_weights.push(weight_samples.clone());
-            dmp.set_weights(weight_samples.clone());
-            let (_,_,car_direction) = race_car.get_position();
-            let planned_trajectory = dmp.clone().generate_trajectory(race_car.get_goal_direction(), car_direction);
-            /*
-            for traj in planned_trajectory.iter() {
-                plot_data.new_data(*traj, 1);
-            }*/
-            let start = SystemTime::now();
-            let seconds = Duration::new(10, 0);
-            let start_reward_count = Duration::new(0,500000);
-            race_car.velocity = 0.1;
-
-            let mut sum_reward = 0.0;
-
-            while (start + seconds) > SystemTime::now() {
-                
-                mq::clear_background(mq::DARKGRAY);
-                let (detected_blue, detected_yellow) = race_car.scan_cones(blue_cones.clone(), yellow_cones.clone());
-                for cone in detected_blue.iter() {
-                    draw_cone(*cone,0.35,mq::BLUE, display);
+        let mut plot_data: PlotData = PlotData::new(300);
+        let mut rollout = Rollout::new(&mut trainer, &vehicle_params);
+        rollout.target_velocity.set(target_velocity);
+
+        let (start_x, start_y, _) = rollout.world.get_track().get_start_pose();
+        display.x = start_x - mq::screen_height()/2.0;
+        display.y = start_y;
+
+        while !rollout.is_done() {
+            rollout.step();
+            let world = &rollout.world;
+            // text, plot and fields of view follow the best sample so far
+            let leader = rollout.get_leader();
+
+            mq::clear_background(mq::DARKGRAY);
+            for i in 0..world.get_n_cars() {
+                let observation = world.get_observation(i);
+                let car = world.get_agent(i).get_car();
+                for detection in observation.blue_cones.iter() {
+                    draw_cone(car.detection_to_world(*detection),CONE_RADIUS as f32,mq::BLUE, display);
                 }
-                for cone in detected_yellow.iter() {
-                    draw_cone(*cone,0.35,mq::YELLOW, display);
+                for detection in observation.yellow_cones.iter() {
+                    draw_cone(car.detection_to_world(*detection),CONE_RADIUS as f32,mq::YELLOW, display);
                 }
-                for cone in orange_cones.iter() {
-                    draw_cone(*cone,0.35,mq::ORANGE, display);
-                }
-
-                let (_,_,car_direction) = race_car.get_position();
-                let index: usize = (race_car.get_traveled_distance()*10.0) as usize;
-                if index < planned_trajectory.len() {
-                    //steering_goal = planned_trajectory[index] * std::f32::consts::PI*2.0;
-                }
-                
-
-                steering_goal = race_car.get_goal_direction()-car_direction;
-                race_car.steering_angle = steering_controller.compute(car_direction, steering_goal);
-                plot_data.new_data(steering_goal, 1);
-                plot_data.new_data((steering_goal-race_car.steering_angle).abs(),0);
-                println!("{}",(race_car.get_goal_direction() ));
-                if race_car.steering_angle > 1.0 {
-                    race_car.steering_angle = 1.0;
-                } else if race_car.steering_angle < -1.0 {
-                    race_car.steering_angle = -1.0;
-                }
-                plot_data.new_data(race_car.steering_angle, 2);
-
-                //println!("steering angle:{}, steering goal: {}, car_direction:{}", race_car.steering_angle,steering_goal, car_direction);
-                race_car.update_car_position();
-                draw_car(race_car, display);
-                let edge_points = race_car.get_view_edge();
-                for cone in edge_points.iter() {
-                    draw_cone(*cone,0.35,mq::GREEN, display);
-                }
-                //plot_data.new_data(steering_goal- car_direction); //% std::f32::consts::PI*2.0
-                let (x,y,_) = race_car.get_position();
-                if (start + start_reward_count) < SystemTime::now() {
-                    sum_reward += dmp.clone().get_reward((x,y));
-                }
-                //plot_data.new_data(dmp.clone().get_reward((x,y)));
-                //plot_data.new_data(sum_reward, 0);
-                plot_data.draw_data();
-
-                // Mouse Actions
-                let (mouse_x, mouse_y) = mq::mouse_position();
-                let (_, mouse_wheel) = mq::mouse_wheel();
-                if mouse_wheel > 0.0 {
-                    display.zoom =  display.zoom+0.1;
-                } else if mouse_wheel < 0.0 {
-                    display.zoom =  display.zoom-0.1;
-                }
-                if mq::is_mouse_button_down(mq::MouseButton::Left) {
-                    if display.mouse_pressed {
-                        display.x += mouse_x-display.m_pressed_x;
-                        display.y += mouse_y-display.m_pressed_y;
-                    }
-                    display.m_pressed_x = mouse_x;
-                    display.m_pressed_y = mouse_y;
-                    display.mouse_pressed = true;
+            }
+            for cone in world.get_track().get_cones(ConeColor::Orange).iter() {
+                draw_cone(*cone,CONE_RADIUS as f32,mq::ORANGE, display);
+            }
+            for i in 0..world.get_n_cars() {
+                for hit in world.get_agent(i).get_cone_hits().iter() {
+                    draw_cone(world.get_track().get_cone(hit.cone),CONE_RADIUS as f32,mq::RED, display);
                 }
-                else {
-                    display.mouse_pressed = false;
+            }
+            for i in 0..world.get_n_cars() {
+                draw_car(world.get_agent(i).get_car(), display);
+            }
+            let race_car = world.get_agent(leader).get_car();
+            for sensor in world.get_agent(leader).get_sensors().iter() {
+                let outline: Vec<(f64,f64)> = sensor.get_field_of_view().iter().map(|point| race_car.vehicle_to_world(*point)).collect();
+                for (from, to) in zip(outline.iter(), outline.iter().skip(1)) {
+                    draw_line(*from, *to, mq::GREEN, display);
                 }
+            }
+            for point in world.get_observation(leader).point_cloud.iter() {
+                draw_cone(race_car.vehicle_to_world((point.x, point.y)),0.05,mq::WHITE, display);
+            }
 
-                // Manual Actions
-                if mq::is_key_down(mq::KeyCode::Right) {
-                    steering_goal += 0.3;
-                } else if mq::is_key_down(mq::KeyCode::Left) {
-                    steering_goal += -0.3;
-                } 
-                if race_car.steering_angle > 1.0 {
-                    race_car.steering_angle = 1.0;
-                } else if race_car.steering_angle < -1.0 {
-                    race_car.steering_angle = -1.0;
-                }
-                if mq::is_key_pressed(mq::KeyCode::Down) {
-                    race_car.velocity -= 0.1;
-                }
-                if mq::is_key_pressed(mq::KeyCode::Up) {
-                    race_car.velocity += 0.1;
+            let steering_goal = world.get_observation(leader).goal_bearing;
+            plot_data.new_data(steering_goal, 1);
+            plot_data.new_data((steering_goal-race_car.get_steering_angle()).abs(),0);
+            plot_data.new_data(race_car.get_steering_angle(), 2);
+            draw_plot(&plot_data);
+            let info = world.get_info(leader);
+            let boundary = info.boundary;
+            let track_text = if boundary.on_track { "on track" } else { "off track" };
+            mq::draw_text(&format!("car {}: {} blue: {:.2} m yellow: {:.2} m", leader, track_text, boundary.distance_to_blue, boundary.distance_to_yellow), 10.0, 20.0, 20.0, mq::WHITE);
+            let lap_timer = world.get_agent(leader).get_lap_timer();
+            let sectors: Vec<String> = lap_timer.get_sector_times().iter().map(|time| format!("{:.2}", time)).collect();
+            mq::draw_text(&format!("lap {}: {:.2} s sectors: [{}]", lap_timer.get_laps().len()+1, lap_timer.get_current_lap_time(info.time), sectors.join(", ")), 10.0, 40.0, 20.0, mq::WHITE);
+            if let Some(lap) = lap_timer.get_laps().last() {
+                mq::draw_text(&format!("last lap: {:.2} s", lap.lap_time), 10.0, 60.0, 20.0, mq::WHITE);
+            }
+
+            // Mouse Actions
+            let (mouse_x, mouse_y) = mq::mouse_position();
+            let (_, mouse_wheel) = mq::mouse_wheel();
+            if mouse_wheel > 0.0 {
+                display.zoom += 0.1;
+            } else if mouse_wheel < 0.0 {
+                display.zoom -= 0.1;
+            }
+            if mq::is_mouse_button_down(mq::MouseButton::Left) {
+                if display.mouse_pressed {
+                    display.x += mouse_x-display.m_pressed_x;
+                    display.y += mouse_y-display.m_pressed_y;
                 }
+                display.m_pressed_x = mouse_x;
+                display.m_pressed_y = mouse_y;
+                display.mouse_pressed = true;
+            }
+            else {
+                display.mouse_pressed = false;
+            }
 
+            // Manual Actions
+            if mq::is_key_pressed(mq::KeyCode::Down) {
+                target_velocity -= 1.0;
+            }
+            if mq::is_key_pressed(mq::KeyCode::Up) {
+                target_velocity += 1.0;
+            }
+            rollout.target_velocity.set(target_velocity);
 
+            mq::next_frame().await
+        }
+        for reward in rollout.sum_rewards.iter() {
+            trainer.add_result(*reward);
+        }
+        trainer.update_weights();
+    }
+}
 
+/// Samples DMP weights around the current mean and moves the mean towards the
+/// best rollouts.
+struct Trainer {
+    n_base_functions: i32,
+    weights: Vec<f32>,
+    sample_rewards: Vec<f32>,
+    sample_weights: Vec<Vec<f32>>,
+}
 
-                mq::next_frame().await
-            }
-            sample_rewards.push(sum_reward);
-            println!("reward: {}", sum_reward);
+impl Trainer {
+    fn new(n_base_functions: i32) -> Trainer {
+        Trainer {
+            n_base_functions,
+            weights: vec![0.0; n_base_functions as usize],
+            sample_rewards: vec![],
+            sample_weights: vec![],
         }
-        // after sample iterations update weights
+    }
+
+    fn sample_weights(&mut self) -> Vec<f32> {
+        let weight_samples: Vec<f32> = self.weights.iter().map(|mean| Normal::new(*mean, 2.0).unwrap().sample( &mut rand::thread_rng())).collect();
+        self.sample_weights.push(weight_samples.clone());
+        weight_samples
+    }
+
+    fn add_result(&mut self, reward: f32) {
+        self.sample_rewards.push(reward);
+        println!("reward: {}", reward);
+    }
+
+    fn update_weights(&mut self) {
         let mut top_sets: Vec<(f32,Vec<f32>)> = vec![];
-        let mut reward_weights: Vec<(f32, Vec<f32>)> = vec![];
-        for (r,w) in zip(sample_rewards.clone(), sample_weights.clone()) {
-            reward_weights.push((r,w));
-        }
+        let mut reward_weights: Vec<(f32, Vec<f32>)> = zip(self.sample_rewards.clone(), self.sample_weights.clone()).collect();
 
-        for i_max in 0..5 {
+        for _ in 0..N_TOP_SAMPLES {
             let index_of_max: Option<usize> = reward_weights.iter()
             .enumerate()
             .max_by(|(_, (a,_)), (_, (b,_))| a.total_cmp(b))
             .map(|(index, _)| index);
             top_sets.push(reward_weights.remove(index_of_max.unwrap()));
         }
-        let mut sum_weights: Vec<f32> = vec![0.0;n_base_functions as usize];
-        for n in 0..top_sets[0].1.len() {
-            for (_,w) in top_sets.clone() {
-                sum_weights[n] += w[n];
+        let mut sum_weights: Vec<f32> = vec![0.0;self.n_base_functions as usize];
+        for (n, sum) in sum_weights.iter_mut().enumerate() {
+            for (_,w) in top_sets.iter() {
+                *sum += w[n];
             }
         }
-        weights = sum_weights.iter().map(|a| a/5.0).collect();
-        println!("weights updated: {:?}",weights);
+        self.weights = sum_weights.iter().map(|a| a/(N_TOP_SAMPLES as f32)).collect();
+        println!("weights updated: {:?}",self.weights);
+    }
+}
 
-        
+/// Drives one car along a DMP sample: PD steering towards the goal bearing
+/// and a velocity hold through throttle and brake.
+#[derive(Serialize, Deserialize)]
+struct DmpController {
+    dmp: DMP,
+    steering_controller: PdController,
+    velocity_controller: PdController,
+    #[serde(skip)]
+    target_velocity: Rc<Cell<f32>>,     // m/s, shared by all cars of a rollout
+    planned_trajectory: Vec<f32>,
+    traveled_distance: f32,
+}
 
+impl DmpController {
+    fn new(weights: Vec<f32>, target_velocity: Rc<Cell<f32>>) -> DmpController {
+        let mut dmp = DMP::new(N_BASE_FUNCTIONS);
+        dmp.set_weights(weights);
+        DmpController {
+            dmp,
+            steering_controller: PdController::new(5.0,0.001*TIME_STEP),    // derivative gain of 0.001 per step
+            velocity_controller: PdController::new(0.5,0.0),
+            target_velocity,
+            planned_trajectory: vec![],
+            traveled_distance: 0.0,
+        }
+    }
+}
 
-        
+impl Controller for DmpController {
+    fn act(&mut self, observation: &Observation, dt: f32) -> Action {
+        if self.planned_trajectory.is_empty() {
+            self.planned_trajectory = self.dmp.generate_trajectory(observation.goal_bearing, 0.0);
+        }
+        self.traveled_distance += observation.velocity*dt;
+        let index: usize = (self.traveled_distance*10.0) as usize;
+        if index < self.planned_trajectory.len() {
+            //steering_goal = self.planned_trajectory[index] * std::f32::consts::PI*2.0;
+        }
 
-        
+        // positive when the car is too fast
+        let velocity_command = self.velocity_controller.compute(observation.velocity, self.target_velocity.get(), dt);
+        Action {
+            steering: self.steering_controller.compute(0.0, observation.goal_bearing, dt),
+            throttle: -velocity_command.min(0.0),
+            brake: velocity_command.max(0.0),
+        }
+    }
 
+    fn snapshot(&self) -> Result<serde_json::Value, serde_json::Error> {
+        save_state(self)
+    }
 
+    /// Keeps the target velocity shared with the other cars.
+    fn restore(&self, state: &serde_json::Value) -> Result<Option<Box<dyn Controller>>, serde_json::Error> {
+        let mut controller = DmpController::deserialize(state)?;
+        controller.target_velocity = self.target_velocity.clone();
+        Ok(Some(Box::new(controller)))
     }
+}
 
+/// One episode of all DMP samples of an update, driven side by side through
+/// each other on a freshly generated track.
+struct Rollout {
+    world: World,
+    target_velocity: Rc<Cell<f32>>,
+    sum_rewards: Vec<f32>,
+}
+
+impl Rollout {
+    fn new(trainer: &mut Trainer, vehicle_params: &VehicleParams) -> Rollout {
+        let mut world = World::new(TIME_STEP, EPISODE_LENGTH);
+        world.set_car_collisions(false);
+        world.set_grid_spacing(0.0);
+        let target_velocity = Rc::new(Cell::new(6.0));
+        for _ in 0..N_SAMPLES {
+            let controller = DmpController::new(trainer.sample_weights(), target_velocity.clone());
+            let index = world.add_car(vehicle_params.clone(), Box::new(controller), Box::<TrackReward>::default());
+            world.set_cone_penalty(index, CONE_PENALTY);
+        }
+        world.reset(rand::random());
+
+        Rollout {
+            world,
+            target_velocity,
+            sum_rewards: vec![0.0; N_SAMPLES],
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.world.is_done()
+    }
+
+    fn step(&mut self) {
+        self.world.step();
+        for (i, sum_reward) in self.sum_rewards.iter_mut().enumerate() {
+            if REWARD_WARMUP < self.world.get_info(i).time {
+                *sum_reward += self.world.get_reward(i);
+            }
+        }
+    }
+
+    /// Index of the car with the highest reward so far.
+    fn get_leader(&self) -> usize {
+        self.sum_rewards.iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+            .unwrap_or(0)
+    }
 }
 
 
@@ -230,6 +371,14 @@ fn draw_cone(cone: (f64,f64), r: f32, color: mq::Color, sc: Screen) {
     mq::draw_circle((mq::screen_width()/2.0 + (cone.0 as f32)*METER_TO_PXL)*sc.zoom+sc.x, (mq::screen_height()/2.0 + (cone.1 as f32)*METER_TO_PXL)*sc.zoom+sc.y, r*sc.zoom * METER_TO_PXL , color);
 }
 
+fn draw_line(from: (f64,f64), to: (f64,f64), color: mq::Color, sc: Screen) {
+    let from_x = (mq::screen_width()/2.0 + (from.0 as f32)*METER_TO_PXL)*sc.zoom+sc.x;
+    let from_y = (mq::screen_height()/2.0 + (from.1 as f32)*METER_TO_PXL)*sc.zoom+sc.y;
+    let to_x = (mq::screen_width()/2.0 + (to.0 as f32)*METER_TO_PXL)*sc.zoom+sc.x;
+    let to_y = (mq::screen_height()/2.0 + (to.1 as f32)*METER_TO_PXL)*sc.zoom+sc.y;
+    mq::draw_line(from_x, from_y, to_x, to_y, 1.0, color);
+}
+
 fn draw_car(car: Car, sc: Screen) {
     let (car_x,car_y,car_direction) = car.get_position();
     let x = (mq::screen_width()/2.0 + car_x * METER_TO_PXL ) * sc.zoom + sc.x;
@@ -250,151 +399,31 @@ fn draw_car(car: Car, sc: Screen) {
     mq::draw_triangle(v1, v2,v3, mq::RED);
 }
 
-
-fn generate_cone_lists() -> (Vec<(f64,f64)>,Vec<(f64,f64)>,Vec<(f64,f64)>) {
-    
-    let center = (0.,0.);
-    let r1 = 40.0;
-    let r2 = 40.0;
-    let n: u32 = 8;
-    let (p, pitch) = get_circle_samples(center, r1, r2, n);
-    let mut pitch_points: Vec<(f64,f64)> = vec![];
-
-    for ((x,y),pit) in zip(p.clone(), pitch.clone()) {
-        pitch_points.push((x+pit.cos()*0.5,y+pit.sin()*0.5));
-    }
-
-    let mut bezier: Vec<(f64, f64)> = vec![];
-    for i in 0..n-1 {
-        let mut bezier_points = bezier_curve(&p[i as usize], pitch[i as usize],&p[(i+1) as usize], pitch[(i+1) as usize], 100);
-        bezier.append(&mut bezier_points);
-    }
-    let mut bezier_points = bezier_curve(&p[(n-1) as usize], pitch[(n-1) as usize],&p[0], pitch[0], 100);
-    bezier.append(&mut bezier_points);
-
-
-
-    let mut last_point: (f64,f64) = (0.0,0.0);  //point[i-1]
-    let mut last_cone: (f64,f64) = (0.0,0.0);
-    let mut blue_cones: Vec<(f64, f64)> = vec![];
-    let mut yellow_cones: Vec<(f64, f64)> = vec![];
-    let mut orange_cones: Vec<(f64, f64)> = vec![];
-    orange_cones.push((bezier[0].0-1.5,bezier[0].1));
-    orange_cones.push((bezier[0].0+1.5,bezier[0].1));
-    for point in bezier.iter() {
-        if get_eukled_distance(last_cone,*point) > 4.0 {
-            if !((last_cone.0 == 0.0) && (last_cone.1 == 0.0 )) {
-                let mut radiant = f64::atan2(last_point.1-point.1, last_point.0-point.0)+ std::f64::consts::PI/2.0;
-                yellow_cones.push((point.0+radiant.cos()*1.5, point.1+radiant.sin()*1.5));
-                radiant = radiant - std::f64::consts::PI;
-                blue_cones.push((point.0+radiant.cos()*1.5, point.1+radiant.sin()*1.5));    
-            }
-            last_cone = *point;
-
+fn draw_plot(plot_data: &PlotData) {
+    mq::draw_rectangle(0.0, 4.0 * mq::screen_height()/5.0, mq::screen_width(), mq::screen_height()/5.0, mq::BLACK);
+    let plot_width = 6.0 * (mq::screen_width()/10.0);
+    let plot_height =  mq::screen_height()/5.0;
+    let width_between_points = plot_width/(plot_data.get_n_data_points() as f32);
+
+    let colors = [mq::RED, mq::GREEN, mq::BLUE];
+    for (i, color) in colors.iter().enumerate().take(plot_data.get_n_plots()) {
+        let data = plot_data.get_data(i);
+        let mut max = data.iter().fold(-f32::INFINITY, |a, &b| a.max(b));
+        let mut min = data.iter().fold(f32::INFINITY, |a, &b| a.min(b));
+        if i > 0 {
+            min = -std::f32::consts::PI;
+            max = std::f32::consts::PI;
         }
-        last_point = *point;
-    }
-    return (blue_cones, yellow_cones,orange_cones);
-
-}
-
-fn get_eukled_distance(p1: (f64,f64), p2:(f64,f64)) -> f64 {
-    return (f64::powf(p1.0-p2.0,2.0)+f64::powf(p1.1-p2.1,2.0)).sqrt()
-}
-
-fn get_circle_samples(center:(f64,f64), r1: f64, r2:f64, n:u32) -> (Vec<(f64, f64)>, Vec<f64>){
-    let mut points: Vec<(f64, f64)>= vec![];
-    let mut pitch: Vec<f64>= vec![];
-
-    for i in 0..n {
-        let diff = std::f64::consts::PI*2.0/f64::from(n);
-        let factor = 0.2;
-        let mut rng =  rand::thread_rng();
-        let rad = f64::from(i)*diff;
-        let x = (r1*rad.cos()+center.0) + rng.gen_range(-r1*factor..r1*factor);
-        let y = (r2*rad.sin()+center.1) + rng.gen_range(-r2*factor..r2*factor);
-        points.push((x,y));
-        pitch.push(rad+std::f64::consts::PI/2.0);
-    }
-    return (points, pitch);
-}
-
-fn bezier_curve(s_point: &(f64,f64), s_rad:f64, e_point: &(f64,f64), e_rad: f64, n:u32)-> Vec<(f64,f64)> {
-    let mut points: Vec<(f64,f64)> = vec![];
-    let dx: f64 = 1.0/f64::from(n);
-    let factor = 10.0;
-    let s_support: (f64,f64)  = (s_point.0+s_rad.cos()*factor,s_point.1+s_rad.sin()*factor);
-    let e_support: (f64,f64)  = (e_point.0+(e_rad-std::f64::consts::PI).cos()*factor,e_point.1+(e_rad-std::f64::consts::PI).sin()*factor);
-    for i in 0..n {
-        let t = dx*f64::from(i);
-        let x:f64 = s_point.0 + t*(-3.0*s_point.0+3.0*s_support.0) + t*t*( 3.0* s_point.0-6.0*s_support.0+ 3.0*e_support.0) + t*t*t* ( -s_point.0+ 3.0*s_support.0-3.0*e_support.0+e_point.0);
-        let y:f64 = s_point.1 + t*(-3.0*s_point.1+3.0*s_support.1) + t*t*( 3.0* s_point.1-6.0*s_support.1+ 3.0*e_support.1) + t*t*t* ( -s_point.1+ 3.0*s_support.1-3.0*e_support.1+e_point.1);
-        points.push((x,y));
-    }
-
-
-    return points;
-
-}
-
-
-struct PlotData {
-    n_data_points: usize,
-    n_plots: usize,
-    data_points: Vec<Vec<f32>>,
-}
-
-impl PlotData {
-    pub fn new(n: i32) -> PlotData {
-        PlotData {
-            n_data_points: n as usize,
-            n_plots: 3,
-            data_points: vec![vec![]; n as usize],
-        }
-    }
-
-    pub fn new_data(&mut self, data_point: f32, n: i32) {
-        self.data_points[n as usize].push(data_point);
-    
-        if self.data_points[n as usize].len() > self.n_data_points {
-            self.data_points[n as usize].remove(0);
+        let min_max = max-min;
+        for (index,point) in data.iter().enumerate() {
+            let y_pos = if min_max != 0.0 {
+                ((max - point)/min_max)*plot_height + 4.0* mq::screen_height()/5.0
+            } else {
+                (0.5)*plot_height + 4.0* mq::screen_height()/5.0
+            };
+            mq::draw_circle((mq::screen_width()/10.0)/2.0 + index as f32 * width_between_points , y_pos , 2.0, *color);
         }
+        mq::draw_line((mq::screen_width()/10.0)/2.0, ((max - 0.0)/min_max)*plot_height + 4.0* mq::screen_height()/5.0,
+                     mq::screen_width(), ((max - 0.0)/min_max)*plot_height + 4.0* mq::screen_height()/5.0, 2.0,*color);
     }
-    pub fn draw_data(&mut self) {
-        mq::draw_rectangle(0.0, 4.0 * mq::screen_height()/5.0, mq::screen_width(), mq::screen_height()/5.0, mq::BLACK);
-        if self.data_points.len() == 0 {
-            return
-        }
-        let plot_width = 6.0 * (mq::screen_width()/10.0);
-        let plot_height =  mq::screen_height()/5.0;
-        let width_between_points = plot_width/(self.n_data_points as f32);
-
-        let colors = vec![mq::RED, mq::GREEN, mq::BLUE];
-        for i in 0..self.n_plots {
-            let mut max = self.data_points[i].iter().fold(-f32::INFINITY, |a, &b| a.max(b));
-            let mut min = self.data_points[i].iter().fold(f32::INFINITY, |a, &b| a.min(b));
-            let mut min_max = max-min;
-            for (index,point) in self.data_points[i].iter().enumerate() {
-                let mut y_pos = 0.0;
-                if i > 0 {
-                    min = -std::f32::consts::PI;
-                    max = std::f32::consts::PI;
-                    min_max = max-min;
-                }
-                if min_max != 0.0 {
-                y_pos = ((max - point)/min_max)*plot_height + 4.0* mq::screen_height()/5.0;
-                } else {
-                    y_pos = (0.5)*plot_height + 4.0* mq::screen_height()/5.0;
-                }
-                mq::draw_circle((mq::screen_width()/10.0)/2.0 + index as f32 * width_between_points , y_pos , 2.0, colors[i]);
-                //mq::draw_text(&format!("{:.2}", max), 0.0, 4.0*mq::screen_height()/5.0, 20.0, mq::WHITE);
-                //mq::draw_text(&format!("{:.2}", min), 0.0, mq::screen_height(), 20.0, mq::WHITE);
-                //mq::draw_text(&format!("{:.2}", self.data_points[i].last().copied().unwrap()), 0.0, mq::screen_height()-mq::screen_height()/10.0, 20.0, mq::WHITE);
-            }
-            mq::draw_line((mq::screen_width()/10.0)/2.0, ((max - 0.0)/min_max)*plot_height + 4.0* mq::screen_height()/5.0,
-                         mq::screen_width(), ((max - 0.0)/min_max)*plot_height + 4.0* mq::screen_height()/5.0, 2.0,colors[i]);
-
-        }
-        
-    }
-}
\ No newline at end of file
+}
@@ -0,0 +1,89 @@
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::car::Car;
+use crate::env::Observation;
+use crate::sensor::{drift, gaussian, DelayLine, SampleTimer, Sensor};
+use crate::track::Track;
+
+/// Noise of the rear wheel speed sensors. The bias starts at zero and drifts
+/// as a random walk, e.g. from tire wear and temperature.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WheelSpeedParams {
+    pub sample_rate: f32,       // Hz
+    pub latency: f32,           // s until a sample is delivered
+    pub speed_std: f32,         // m/s
+    pub bias_drift: f32,        // m/s per sqrt(s)
+}
+
+impl Default for WheelSpeedParams {
+    fn default() -> WheelSpeedParams {
+        WheelSpeedParams {
+            sample_rate: 50.0,
+            latency: 0.0,
+            speed_std: 0.05,
+            bias_drift: 0.001,
+        }
+    }
+}
+
+/// One sample of the rear wheel speeds.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct WheelSpeedReading {
+    pub time: f32,          // s, when the sample was taken
+    pub rear_left: f32,     // m/s, wheel on the side of increasing direction
+    pub rear_right: f32,    // m/s
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WheelSpeedSensor {
+    params: WheelSpeedParams,
+    timer: SampleTimer,
+    delay: DelayLine<WheelSpeedReading>,
+    delivered: Vec<WheelSpeedReading>,  // since the last observation
+    left_bias: f32,
+    right_bias: f32,
+}
+
+impl WheelSpeedSensor {
+    pub fn new(params: WheelSpeedParams) -> WheelSpeedSensor {
+        WheelSpeedSensor {
+            params,
+            timer: SampleTimer::new(params.sample_rate),
+            delay: DelayLine::new(params.latency),
+            delivered: vec![],
+            left_bias: 0.0,
+            right_bias: 0.0,
+        }
+    }
+}
+
+impl Sensor for WheelSpeedSensor {
+    fn reset(&mut self) {
+        *self = WheelSpeedSensor::new(self.params);
+    }
+
+    /// Drifts the biases by `dt` seconds and samples the wheel speeds as often
+    /// as the sample rate asks for, each reading stamped with the time it was
+    /// due. A wheel speed sensor can not measure below zero.
+    fn update(&mut self, car: Car, _track: &Track, time: f32, dt: f32, rng: &mut ChaCha8Rng) {
+        let params = self.params;
+        self.left_bias = drift(self.left_bias, params.bias_drift, dt, rng);
+        self.right_bias = drift(self.right_bias, params.bias_drift, dt, rng);
+        let (left, right) = car.get_rear_wheel_speeds();
+        for offset in self.timer.advance(dt) {
+            let sample_time = time - dt + offset;
+            self.delay.push(sample_time, WheelSpeedReading {
+                time: sample_time,
+                rear_left: (left + self.left_bias + gaussian(params.speed_std, rng)).max(0.0),
+                rear_right: (right + self.right_bias + gaussian(params.speed_std, rng)).max(0.0),
+            });
+        }
+        self.delay.deliver(time, &mut self.delivered);
+    }
+
+    fn observe(&mut self, observation: &mut Observation) {
+        observation.wheel_speeds.append(&mut self.delivered);
+    }
+}
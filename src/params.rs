@@ -0,0 +1,332 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{Camera, CameraParams};
+use crate::cone_sensor::{ConeSensor, ConeSensorParams};
+use crate::dynamics::{Powertrain, SteeringActuator, VehicleModel};
+use crate::gnss::{Gnss, GnssParams};
+use crate::imu::{Imu, ImuParams};
+use crate::lidar::{Lidar, LidarParams};
+use crate::odometry::{WheelSpeedParams, WheelSpeedSensor};
+use crate::sensor::{Sensor, MAX_SAMPLE_RATE};
+
+/// Keys of the sensors before any number of them could be mounted, with what
+/// replaces them in a `[[sensors]]` entry.
+const MOVED_SENSOR_KEYS: [(&str, &str); 11] = [
+    ("view_angle", "type = \"cones\" and view_angle"),
+    ("view_range", "type = \"cones\" and view_range"),
+    ("cone_noise", "type = \"cones\" and noise"),
+    ("cone_occlusion", "type = \"cones\" and occlusion"),
+    ("cone_sample_rate", "type = \"cones\" and sample_rate"),
+    ("cone_latency", "type = \"cones\" and latency"),
+    ("lidar", "type = \"lidar\""),
+    ("camera", "type = \"camera\""),
+    ("imu", "type = \"imu\""),
+    ("wheel_speed", "type = \"wheel_speed\""),
+    ("gnss", "type = \"gnss\""),
+];
+
+/// Setup of a car. Missing keys in a file fall back to the defaults, which
+/// describe the car `Car::new` creates, unknown keys are an error.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VehicleParams {
+    pub max_steering_angle: f32,    // rad at steering angle 1.0 / -1.0
+    pub track_width: f32,           // m, distance between left and right wheel
+    pub wheelbase: f32,             // m, distance between front and rear axis
+    pub mass: f32,                  // kg
+    pub model: VehicleModel,
+    pub powertrain: Powertrain,
+    pub steering: SteeringActuator,
+    pub sensors: Vec<SensorParams>,
+}
+
+impl Default for VehicleParams {
+    fn default() -> VehicleParams {
+        VehicleParams {
+            max_steering_angle: std::f32::consts::PI/8.0,
+            track_width: 2.0,
+            wheelbase: 2.0,
+            mass: 250.0,
+            model: VehicleModel::Kinematic,
+            powertrain: Powertrain::default(),
+            steering: SteeringActuator::default(),
+            sensors: vec![SensorParams::Cones(ConeSensorParams::default())],
+        }
+    }
+}
+
+impl VehicleParams {
+    /// Loads parameters from a `.toml` or `.json` file and checks them with
+    /// `validate`. Files with the sensor keys of older versions are rejected
+    /// with the `[[sensors]]` entry that replaces them.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<VehicleParams, ParamsError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let params: VehicleParams = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => {
+                let table: toml::Table = toml::from_str(&content)?;
+                check_moved_keys(table.keys())?;
+                toml::from_str(&content)?   // again from the text, so errors point at their line
+            }
+            Some("json") => {
+                let value: serde_json::Value = serde_json::from_str(&content)?;
+                if let Some(object) = value.as_object() {
+                    check_moved_keys(object.keys())?;
+                }
+                serde_json::from_value(value)?
+            }
+            _ => return Err(ParamsError::UnknownFormat(path.display().to_string())),
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Rejects parameters the vehicle models can not integrate, such as a
+    /// zero mass, a center of gravity outside the wheelbase or a steering
+    /// angle that turns the inner rear wheel on the spot, and sensors that
+    /// can not be simulated.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        let positive = [
+            ("max_steering_angle", self.max_steering_angle),
+            ("track_width", self.track_width),
+            ("wheelbase", self.wheelbase),
+            ("mass", self.mass),
+        ];
+        let non_negative = [
+            ("powertrain.max_drive_force", self.powertrain.max_drive_force),
+            ("powertrain.max_brake_force", self.powertrain.max_brake_force),
+            ("powertrain.drag_coefficient", self.powertrain.drag_coefficient),
+            ("powertrain.rolling_resistance", self.powertrain.rolling_resistance),
+            ("powertrain.max_acceleration", self.powertrain.max_acceleration),
+            ("powertrain.max_deceleration", self.powertrain.max_deceleration),
+            ("steering.max_rate", self.steering.max_rate),
+            ("steering.time_constant", self.steering.time_constant),
+        ];
+        for (key, value) in positive {
+            check_positive(key, value)?;
+        }
+        for (key, value) in non_negative {
+            check_non_negative(key, value)?;
+        }
+        // at atan(2*wheelbase/track_width) the center of the turn reaches a rear wheel and the kinematic model divides by zero
+        let max_turning_angle = (2.0*self.wheelbase/self.track_width).atan();
+        if self.max_steering_angle >= max_turning_angle {
+            return Err(ParamsError::Invalid(format!("max_steering_angle has to be below {} rad for this wheelbase and track width, is {}", max_turning_angle, self.max_steering_angle)));
+        }
+        if let VehicleModel::Dynamic(bicycle) = self.model {
+            if !(bicycle.yaw_inertia > 0.0 && bicycle.yaw_inertia.is_finite()) {
+                return Err(ParamsError::Invalid(format!("model.yaw_inertia has to be positive, is {}", bicycle.yaw_inertia)));
+            }
+            if !(0.0..=self.wheelbase).contains(&bicycle.cg_to_rear) {
+                return Err(ParamsError::Invalid(format!("model.cg_to_rear has to be between 0.0 and the wheelbase of {} m, is {}", self.wheelbase, bicycle.cg_to_rear)));
+            }
+        }
+        for (i, sensor) in self.sensors.iter().enumerate() {
+            sensor.validate(&format!("sensors[{}]", i))?;
+        }
+        Ok(())
+    }
+
+    /// Sensors of the car, in the order of `sensors`.
+    pub fn build_sensors(&self) -> Vec<Box<dyn Sensor>> {
+        self.sensors.iter().enumerate().map(|(i, sensor)| sensor.build(i)).collect()
+    }
+}
+
+fn check_positive(key: &str, value: f32) -> Result<(), ParamsError> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(ParamsError::Invalid(format!("{} has to be finite and positive, is {}", key, value)))
+    }
+}
+
+fn check_non_negative(key: &str, value: f32) -> Result<(), ParamsError> {
+    if value >= 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(ParamsError::Invalid(format!("{} has to be finite and not negative, is {}", key, value)))
+    }
+}
+
+fn check_probability(key: &str, value: f32) -> Result<(), ParamsError> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(())
+    } else {
+        Err(ParamsError::Invalid(format!("{} has to be between 0.0 and 1.0, is {}", key, value)))
+    }
+}
+
+fn check_sample_rate(key: &str, value: f32) -> Result<(), ParamsError> {
+    if (0.0..=MAX_SAMPLE_RATE).contains(&value) {
+        Ok(())
+    } else {
+        Err(ParamsError::Invalid(format!("{} has to be between 0.0 and {} Hz, is {}", key, MAX_SAMPLE_RATE, value)))
+    }
+}
+
+fn check_moved_keys<'a>(keys: impl Iterator<Item = &'a String>) -> Result<(), ParamsError> {
+    for key in keys {
+        if let Some((key, replacement)) = MOVED_SENSOR_KEYS.iter().find(|(moved, _)| moved == key) {
+            return Err(ParamsError::MovedKey { key: key.to_string(), replacement: replacement.to_string() });
+        }
+    }
+    Ok(())
+}
+
+/// One sensor of the car, `type` selects the kind in a file.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SensorParams {
+    Cones(ConeSensorParams),
+    Lidar(LidarParams),
+    Camera(CameraParams),
+    Imu(ImuParams),
+    WheelSpeed(WheelSpeedParams),
+    Gnss(GnssParams),
+}
+
+impl SensorParams {
+    /// `index` is the position of the sensor among the sensors of the car.
+    pub fn build(&self, index: usize) -> Box<dyn Sensor> {
+        match self {
+            SensorParams::Cones(params) => Box::new(ConeSensor::new(*params)),
+            SensorParams::Lidar(params) => Box::new(Lidar::new(*params)),
+            SensorParams::Camera(params) => Box::new(Camera::new(*params, index)),
+            SensorParams::Imu(params) => Box::new(Imu::new(*params)),
+            SensorParams::WheelSpeed(params) => Box::new(WheelSpeedSensor::new(*params)),
+            SensorParams::Gnss(params) => Box::new(Gnss::new(params.clone())),
+        }
+    }
+
+    /// Rejects values the sensor can not be simulated with, `key` is where
+    /// the sensor is in the file.
+    fn validate(&self, key: &str) -> Result<(), ParamsError> {
+        let (sample_rates, non_negative, probabilities) = match self {
+            SensorParams::Cones(cones) => (
+                cones.sample_rate.map(|rate| ("sample_rate", rate)).into_iter().collect(),
+                vec![
+                    ("latency", cones.latency),
+                    ("noise.range_std", cones.noise.range_std),
+                    ("noise.bearing_std", cones.noise.bearing_std),
+                    ("noise.false_positives", cones.noise.false_positives),
+                ],
+                vec![
+                    ("noise.detection_probability", cones.noise.detection_probability),
+                    ("noise.detection_probability_at_range", cones.noise.detection_probability_at_range),
+                    ("noise.duplicate_probability", cones.noise.duplicate_probability),
+                ],
+            ),
+            SensorParams::Lidar(lidar) => {
+                check_positive(&format!("{}.angular_resolution", key), lidar.angular_resolution)?;
+                check_positive(&format!("{}.max_range", key), lidar.max_range)?;
+                if lidar.channels == 0 {
+                    return Err(ParamsError::Invalid(format!("{}.channels has to be at least 1", key)));
+                }
+                (vec![], vec![("rotation_rate", lidar.rotation_rate), ("range_std", lidar.range_std), ("latency", lidar.latency)], vec![])
+            }
+            SensorParams::Camera(camera) => (
+                vec![("frame_rate", camera.frame_rate)],
+                vec![("latency", camera.latency)],
+                vec![
+                    ("detection_probability", camera.detection_probability),
+                    ("detection_probability_at_range", camera.detection_probability_at_range),
+                    ("misclassification_probability", camera.misclassification_probability),
+                    ("min_visible_fraction", camera.min_visible_fraction),
+                ],
+            ),
+            SensorParams::Imu(imu) => (
+                vec![("sample_rate", imu.sample_rate)],
+                vec![
+                    ("latency", imu.latency),
+                    ("yaw_rate_std", imu.yaw_rate_std),
+                    ("acceleration_std", imu.acceleration_std),
+                    ("yaw_rate_bias_drift", imu.yaw_rate_bias_drift),
+                    ("acceleration_bias_drift", imu.acceleration_bias_drift),
+                ],
+                vec![],
+            ),
+            SensorParams::WheelSpeed(wheel_speed) => (
+                vec![("sample_rate", wheel_speed.sample_rate)],
+                vec![("latency", wheel_speed.latency), ("speed_std", wheel_speed.speed_std), ("bias_drift", wheel_speed.bias_drift)],
+                vec![],
+            ),
+            SensorParams::Gnss(gnss) => (
+                vec![("sample_rate", gnss.sample_rate)],
+                vec![
+                    ("latency", gnss.latency),
+                    ("position_std", gnss.position_std),
+                    ("heading_std", gnss.heading_std),
+                    ("position_drift", gnss.position_drift),
+                    ("multipath_std", gnss.multipath_std),
+                    ("outage_rate", gnss.outage_rate),
+                    ("outage_duration", gnss.outage_duration),
+                ],
+                vec![("multipath_probability", gnss.multipath_probability)],
+            ),
+        };
+        if let SensorParams::Gnss(gnss) = self {
+            for (i, (start, end)) in gnss.outage_windows.iter().enumerate() {
+                if !(start.is_finite() && end.is_finite() && start <= end) {
+                    return Err(ParamsError::Invalid(format!("{}.outage_windows[{}] has to end after it starts, is [{}, {}]", key, i, start, end)));
+                }
+            }
+        }
+        for (name, value) in sample_rates {
+            check_sample_rate(&format!("{}.{}", key, name), value)?;
+        }
+        for (name, value) in non_negative {
+            check_non_negative(&format!("{}.{}", key, name), value)?;
+        }
+        for (name, value) in probabilities {
+            check_probability(&format!("{}.{}", key, name), value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ParamsError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    UnknownFormat(String),
+    Invalid(String),    // what is wrong with which key
+    MovedKey { key: String, replacement: String },  // sensor key of an older version and what replaces it
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamsError::Io(error) => write!(f, "could not read vehicle parameters: {}", error),
+            ParamsError::Toml(error) => write!(f, "invalid toml vehicle parameters: {}", error),
+            ParamsError::Json(error) => write!(f, "invalid json vehicle parameters: {}", error),
+            ParamsError::UnknownFormat(path) => write!(f, "{} is neither a .toml nor a .json file", path),
+            ParamsError::Invalid(reason) => write!(f, "invalid vehicle parameters: {}", reason),
+            ParamsError::MovedKey { key, replacement } => write!(f, "{} moved into the sensor list, use a [[sensors]] entry with {}", key, replacement),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+impl From<std::io::Error> for ParamsError {
+    fn from(error: std::io::Error) -> ParamsError {
+        ParamsError::Io(error)
+    }
+}
+
+impl From<toml::de::Error> for ParamsError {
+    fn from(error: toml::de::Error) -> ParamsError {
+        ParamsError::Toml(error)
+    }
+}
+
+impl From<serde_json::Error> for ParamsError {
+    fn from(error: serde_json::Error) -> ParamsError {
+        ParamsError::Json(error)
+    }
+}
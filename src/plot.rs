@@ -0,0 +1,37 @@
+/// Rolling buffers of the last `n_data_points` values for a few plots.
+/// Drawing is left to the front-end.
+pub struct PlotData {
+    n_data_points: usize,
+    n_plots: usize,
+    data_points: Vec<Vec<f32>>,
+}
+
+impl PlotData {
+    pub fn new(n: i32) -> PlotData {
+        PlotData {
+            n_data_points: n as usize,
+            n_plots: 3,
+            data_points: vec![vec![]; 3],
+        }
+    }
+
+    pub fn new_data(&mut self, data_point: f32, n: i32) {
+        self.data_points[n as usize].push(data_point);
+    
+        if self.data_points[n as usize].len() > self.n_data_points {
+            self.data_points[n as usize].remove(0);
+        }
+    }
+
+    pub fn get_n_data_points(&self) -> usize {
+        self.n_data_points
+    }
+
+    pub fn get_n_plots(&self) -> usize {
+        self.n_plots
+    }
+
+    pub fn get_data(&self, n: usize) -> &[f32] {
+        &self.data_points[n]
+    }
+}
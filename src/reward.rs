@@ -0,0 +1,82 @@
+use std::iter::zip;
+
+use serde::{Deserialize, Serialize};
+
+use crate::car::Car;
+use crate::env::StepInfo;
+use crate::snapshot::save_state;
+use crate::track::{ConeColor, Track};
+
+const BASE_REWARD: f32 = 100.0;
+
+/// Reward signal of an environment. `reset` is called with every newly
+/// generated track before the first `get_reward` of an episode, `info` holds
+/// the events of the step such as cone hits and lap times. Rewards with state
+/// across steps save it in `snapshot` and load it in `restore`.
+pub trait RewardFunction {
+    fn reset(&mut self, track: &Track);
+    fn get_reward(&mut self, car: &Car, info: &StepInfo) -> f32;
+
+    fn snapshot(&self) -> Result<serde_json::Value, serde_json::Error> {
+        Ok(serde_json::Value::Null)
+    }
+
+    /// A new reward function in the state of `state`, `self` is left as it
+    /// is. `None` keeps using `self`, e.g. for rewards without state.
+    fn restore(&self, _state: &serde_json::Value) -> Result<Option<Box<dyn RewardFunction>>, serde_json::Error> {
+        Ok(None)
+    }
+}
+
+/// Rewards driving close to the center line between the blue and yellow
+/// cones, with later parts of the track worth more.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TrackReward {
+    reward_trajectory: Vec<(f64,f64,f32)>,
+    last_reward: f32,
+}
+
+impl TrackReward {
+    pub fn new(track: &Track) -> TrackReward {
+        let mut reward = TrackReward::default();
+        reward.reset(track);
+        reward
+    }
+}
+
+impl RewardFunction for TrackReward {
+    fn reset(&mut self, track: &Track) {
+        self.reward_trajectory.clear();
+        for (i, (b_cone, y_cone)) in zip(track.get_cones(ConeColor::Yellow), track.get_cones(ConeColor::Blue)).enumerate() {
+            let x = (b_cone.0 + y_cone.0)/2.0;
+            let y = (b_cone.1 + y_cone.1)/2.0;
+            let reward: f32 = i as f32 + BASE_REWARD;
+            self.reward_trajectory.push((x,y,reward));
+        }
+        self.last_reward = 0.0;
+    }
+
+    fn get_reward(&mut self, car: &Car, _info: &StepInfo) -> f32 {
+        let (pos_x, pos_y, _) = car.get_position();
+        let mut reward: f32 = 0.0;
+        for traj in self.reward_trajectory.iter() {
+            let distance = ((pos_x - traj.0 as f32).powf(2.0) + (pos_y - traj.1 as f32).powf(2.0)).sqrt();
+            if distance < 4.0 {
+                reward += (traj.2 / (distance* distance+ 2.0))*0.01;
+            }
+        }
+        if self.last_reward.abs() < 0.01 {
+            reward += self.last_reward;
+        }
+        self.last_reward = reward;
+        reward
+    }
+
+    fn snapshot(&self) -> Result<serde_json::Value, serde_json::Error> {
+        save_state(self)
+    }
+
+    fn restore(&self, state: &serde_json::Value) -> Result<Option<Box<dyn RewardFunction>>, serde_json::Error> {
+        Ok(Some(Box::new(TrackReward::deserialize(state)?)))
+    }
+}
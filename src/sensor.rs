@@ -0,0 +1,307 @@
+use std::collections::VecDeque;
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Normal, Poisson};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::car::Car;
+use crate::env::Observation;
+use crate::snapshot::save_state;
+use crate::track::Track;
+
+/// A sensor mounted on a car. `update` advances it together with the car and
+/// `observe` moves what it delivered since the last observation into the
+/// observation of the car. Its serde representation is its state across
+/// steps, saved and loaded through `SensorState`.
+pub trait Sensor: SensorState {
+    /// Mounting pose as m in front of the rear axle, m towards increasing
+    /// direction and rad relative to the heading. The default is the rear axle.
+    fn get_mount(&self) -> (f32,f32,f32) {
+        (0.0, 0.0, 0.0)
+    }
+
+    /// Outline of the field of view in the vehicle frame, empty for sensors
+    /// that do not look at the track.
+    fn get_field_of_view(&self) -> Vec<(f32,f32)> {
+        vec![]
+    }
+
+    /// Back to the state before the first sample of an episode.
+    fn reset(&mut self);
+
+    /// Advances the sensor by `dt` seconds up to `time`, `car` is the true
+    /// state at `time`.
+    fn update(&mut self, car: Car, track: &Track, time: f32, dt: f32, rng: &mut ChaCha8Rng);
+
+    fn observe(&mut self, observation: &mut Observation);
+}
+
+/// Snapshot and restore of a sensor, implemented for every sensor that
+/// serializes.
+pub trait SensorState {
+    fn snapshot(&self) -> Result<serde_json::Value, serde_json::Error>;
+
+    /// A new sensor in the state of `state`, `self` is left as it is.
+    fn restore(&self, state: &serde_json::Value) -> Result<Box<dyn Sensor>, serde_json::Error>;
+}
+
+impl<T: Sensor + Serialize + DeserializeOwned + 'static> SensorState for T {
+    fn snapshot(&self) -> Result<serde_json::Value, serde_json::Error> {
+        save_state(self)
+    }
+
+    fn restore(&self, state: &serde_json::Value) -> Result<Box<dyn Sensor>, serde_json::Error> {
+        Ok(Box::new(T::deserialize(state)?))
+    }
+}
+
+/// Position in the vehicle frame of a point `range` m away at `bearing` from
+/// a sensor mounted at `mount`.
+pub fn sensor_to_vehicle(mount: (f32,f32,f32), range: f32, bearing: f32) -> (f32,f32) {
+    let angle = mount.2 + bearing;
+    (mount.0 + range*angle.cos(), mount.1 + range*angle.sin())
+}
+
+/// World position of a sensor mounted at `mount` and the world angle of its
+/// yaw, from the true pose of `car`.
+pub fn get_mount_pose(mount: (f32,f32,f32), car: Car) -> (f32,f32,f32) {
+    let (x, y, direction) = car.get_position();
+    (x + mount.0*direction.cos() - mount.1*direction.sin(),
+     y + mount.0*direction.sin() + mount.1*direction.cos(),
+     direction + mount.2)
+}
+
+/// Outline of a circular sector of `angle` rad and `range` m looking along the
+/// yaw of `mount`, in the vehicle frame.
+pub fn get_sector_outline(mount: (f32,f32,f32), angle: f32, range: f32) -> Vec<(f32,f32)> {
+    let n = 20;
+    let angle = angle.min(std::f32::consts::PI*2.0);
+    let mut outline = vec![(mount.0, mount.1)];
+    for i in 0..=n {
+        let bearing = -angle/2.0 + angle*(i as f32)/(n as f32);
+        outline.push(sensor_to_vehicle(mount, range, bearing));
+    }
+    outline.push((mount.0, mount.1));
+    outline
+}
+
+/// Slack in s when comparing times that are sums of steps, so a period or a
+/// latency that is a multiple of the step is not missed by rounding.
+const TIME_TOLERANCE: f32 = 1e-4;
+
+/// Highest sample rate in Hz, faster periods vanish in the tolerance.
+pub const MAX_SAMPLE_RATE: f32 = 1.0/TIME_TOLERANCE;
+
+/// A cone as the car perceives it, relative to its rear axle and heading.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Detection {
+    pub time: f32,      // s, when the scan was taken
+    pub range: f32,     // m
+    pub bearing: f32,   // rad from the heading, positive towards increasing direction
+}
+
+impl Detection {
+    /// Wraps the bearing to (-pi, pi].
+    pub fn new(time: f32, range: f32, bearing: f32) -> Detection {
+        Detection {
+            time,
+            range,
+            bearing: bearing.sin().atan2(bearing.cos()),
+        }
+    }
+
+    /// Position in the vehicle frame, x forward and y towards increasing direction.
+    pub fn get_local_position(self) -> (f32,f32) {
+        (self.range*self.bearing.cos(), self.range*self.bearing.sin())
+    }
+}
+
+/// Errors of the cone detection in `ConeSensor::scan`. The default is a
+/// perfect sensor.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConeNoise {
+    pub range_std: f32,                         // m, standard deviation of the measured distance
+    pub bearing_std: f32,                       // rad, standard deviation of the measured bearing
+    pub detection_probability: f32,             // of a cone right in front of the car
+    pub detection_probability_at_range: f32,    // of a cone at the view range, linear in between
+    pub false_positives: f32,                   // mean number of detections without a cone per scan
+    pub duplicate_probability: f32,             // of a cone being reported twice
+}
+
+impl ConeNoise {
+    /// Probability of detecting a cone `distance` m away with a sensor
+    /// reaching `view_range` m.
+    pub fn get_detection_probability(self, distance: f32, view_range: f32) -> f32 {
+        get_detection_probability(self.detection_probability, self.detection_probability_at_range, distance, view_range)
+    }
+
+    /// Detection of a cone at `range` and `bearing` in a scan taken at `time`.
+    pub fn measure<R: Rng>(self, time: f32, range: f32, bearing: f32, rng: &mut R) -> Detection {
+        let range = range + gaussian(self.range_std, rng);
+        let bearing = bearing + gaussian(self.bearing_std, rng);
+        Detection::new(time, range.max(0.0), bearing)
+    }
+
+    pub fn sample_false_positive_count<R: Rng>(self, rng: &mut R) -> usize {
+        match Poisson::new(self.false_positives as f64) {
+            Ok(poisson) => poisson.sample(rng) as usize,
+            Err(_) => 0,    // rate is 0.0 or negative
+        }
+    }
+}
+
+impl Default for ConeNoise {
+    fn default() -> ConeNoise {
+        ConeNoise {
+            range_std: 0.0,
+            bearing_std: 0.0,
+            detection_probability: 1.0,
+            detection_probability_at_range: 1.0,
+            false_positives: 0.0,
+            duplicate_probability: 0.0,
+        }
+    }
+}
+
+/// Probability of detecting a cone `distance` m away, `near` right in front
+/// of the sensor and `at_range` at its `range`, linear in between.
+pub fn get_detection_probability(near: f32, at_range: f32, distance: f32, range: f32) -> f32 {
+    let fraction = if range > 0.0 { (distance/range).clamp(0.0, 1.0) } else { 0.0 };
+    near + (at_range-near)*fraction
+}
+
+/// Zero-mean gaussian sample, 0.0 for a standard deviation of 0.0.
+pub fn gaussian<R: Rng>(std_dev: f32, rng: &mut R) -> f32 {
+    match Normal::new(0.0, std_dev) {
+        Ok(normal) if std_dev > 0.0 => normal.sample(rng),
+        _ => 0.0,
+    }
+}
+
+/// Bias after a random walk of `dt` seconds, `drift` is the standard
+/// deviation of the change per square root of a second.
+pub fn drift<R: Rng>(bias: f32, drift: f32, dt: f32, rng: &mut R) -> f32 {
+    bias + gaussian(drift*dt.max(0.0).sqrt(), rng)
+}
+
+/// Finds the samples of a sensor with a fixed sample rate that fall into
+/// each step. The rate of every sensor, `sample_rate` or `frame_rate`, is in
+/// Hz and 0.0 never samples. Only the cone sensor can leave its rate out to
+/// follow the steps instead, see `every_step`.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct SampleTimer {
+    period: Option<f32>,    // s between two samples, None never samples
+    elapsed: f32,           // s since the last sample
+}
+
+impl SampleTimer {
+    /// `sample_rate` in Hz, the first sample is due right away.
+    pub fn new(sample_rate: f32) -> SampleTimer {
+        let period = if sample_rate > 0.0 { Some(1.0/sample_rate) } else { None };
+        SampleTimer {
+            period,
+            elapsed: period.unwrap_or(0.0),
+        }
+    }
+
+    /// Samples once at the end of every step, whatever its length.
+    pub fn every_step() -> SampleTimer {
+        SampleTimer {
+            period: Some(0.0),
+            elapsed: 0.0,
+        }
+    }
+
+    /// Advances by `dt` seconds and returns when the samples due in this step
+    /// were taken, in seconds after the start of the step.
+    pub fn advance(&mut self, dt: f32) -> DueSamples {
+        let mut due = DueSamples {
+            first: dt,
+            period: 0.0,
+            dt,
+            remaining: 0,
+        };
+        match self.period {
+            None => {}
+            Some(period) if period <= 0.0 => due.remaining = 1,
+            Some(period) => {
+                due.first = period - self.elapsed;
+                due.period = period;
+                self.elapsed += dt;
+                while self.elapsed + TIME_TOLERANCE >= period {
+                    self.elapsed -= period;
+                    due.remaining += 1;
+                }
+            }
+        }
+        due
+    }
+}
+
+/// Times of the samples due in one step, see [`SampleTimer::advance`].
+pub struct DueSamples {
+    first: f32,         // s after the start of the step
+    period: f32,        // s
+    dt: f32,            // s
+    remaining: usize,
+}
+
+impl Iterator for DueSamples {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let offset = self.first.clamp(0.0, self.dt);
+        self.first += self.period;
+        Some(offset)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for DueSamples {}
+
+/// Holds measurements back until the latency of their sensor has passed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DelayLine<T> {
+    latency: f32,                   // s
+    pending: VecDeque<(f32, T)>,    // delivery time and measurement, oldest first
+}
+
+impl<T> DelayLine<T> {
+    pub fn new(latency: f32) -> DelayLine<T> {
+        DelayLine {
+            latency: latency.max(0.0),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Queues a measurement taken at `time`.
+    pub fn push(&mut self, time: f32, measurement: T) {
+        self.pending.push_back((time + self.latency, measurement));
+    }
+
+    /// Oldest measurement that has arrived at `time`, if any.
+    pub fn pop(&mut self, time: f32) -> Option<T> {
+        if self.pending.front().is_some_and(|(delivery_time, _)| *delivery_time <= time + TIME_TOLERANCE) {
+            self.pending.pop_front().map(|(_, measurement)| measurement)
+        } else {
+            None
+        }
+    }
+
+    /// Moves the measurements that have arrived at `time` to `delivered`.
+    pub fn deliver(&mut self, time: f32, delivered: &mut Vec<T>) {
+        while let Some(measurement) = self.pop(time) {
+            delivered.push(measurement);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+use std::path::Path;
+
+use rand_chacha::ChaCha8Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::boundary::BoundaryStatus;
+use crate::car::Car;
+use crate::clock::SimClock;
+use crate::collision::{CarHit, ConeHit};
+use crate::env::{Observation, StepInfo};
+use crate::params::VehicleParams;
+use crate::timing::LapTimer;
+use crate::track::{ConeId, Track};
+
+/// Complete state of an `Agent`. The reward function and the sensors store
+/// their own state through `RewardFunction::snapshot` and `Sensor::snapshot`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AgentSnapshot {
+    pub car: Car,
+    pub vehicle_params: VehicleParams,
+    pub reward: serde_json::Value,
+    pub lap_timer: LapTimer,
+    pub boundary_status: BoundaryStatus,
+    pub cone_penalty: f32,
+    pub cone_contacts: Vec<ConeId>,
+    pub cone_hits: Vec<ConeHit>,
+    pub car_contacts: Vec<usize>,
+    pub car_hits: Vec<CarHit>,
+    pub sensors: Vec<serde_json::Value>,
+    pub rng: ChaCha8Rng,
+    pub time: f32,
+    pub goal_direction: f32,
+}
+
+/// Complete state of an `Env`, restoring it continues the episode exactly
+/// where the snapshot was taken.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EnvSnapshot {
+    pub track: Track,
+    pub clock: SimClock,
+    pub episode_length: f32,
+    pub end_on_off_track: bool,
+    pub agent: AgentSnapshot,
+    pub observation: Observation,
+}
+
+/// Complete state of a `World`. Controllers store their own state through
+/// `Controller::snapshot`, in the order the cars were added.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub track: Track,
+    pub clock: SimClock,
+    pub episode_length: f32,
+    pub end_on_off_track: bool,
+    pub car_collisions: bool,
+    pub grid_spacing: f32,
+    pub agents: Vec<AgentSnapshot>,
+    pub controllers: Vec<serde_json::Value>,
+    pub observations: Vec<Observation>,
+    pub infos: Vec<StepInfo>,
+    pub rewards: Vec<f32>,
+}
+
+impl EnvSnapshot {
+    /// Writes the snapshot as json.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        save(self, path)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<EnvSnapshot, SnapshotError> {
+        from_file(path)
+    }
+}
+
+impl WorldSnapshot {
+    /// Writes the snapshot as json.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        save(self, path)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<WorldSnapshot, SnapshotError> {
+        from_file(path)
+    }
+}
+
+/// Serialized state of a controller, reward function or sensor for its
+/// `snapshot`. It is read back once, so a value json can not hold, such as a
+/// NaN that becomes null, fails here and not when restoring.
+pub fn save_state<T: Serialize + DeserializeOwned>(value: &T) -> Result<serde_json::Value, serde_json::Error> {
+    let state = serde_json::to_value(value)?;
+    T::deserialize(&state)?;
+    Ok(state)
+}
+
+fn save<T: Serialize, P: AsRef<Path>>(snapshot: &T, path: P) -> Result<(), SnapshotError> {
+    std::fs::write(path, serde_json::to_string(snapshot)?)?;
+    Ok(())
+}
+
+fn from_file<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, SnapshotError> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    CarCount { expected: usize, found: usize },   // cars in the world vs. in the snapshot
+    ControllerCount { expected: usize, found: usize },    // controllers in the world vs. in the snapshot
+    SensorCount { expected: usize, found: usize },    // sensors of the car vs. in the snapshot
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "could not access snapshot: {}", error),
+            SnapshotError::Json(error) => write!(f, "invalid snapshot: {}", error),
+            SnapshotError::CarCount { expected, found } => write!(f, "snapshot holds {} cars, the world {}", found, expected),
+            SnapshotError::ControllerCount { expected, found } => write!(f, "snapshot holds {} controllers, the world {}", found, expected),
+            SnapshotError::SensorCount { expected, found } => write!(f, "snapshot holds {} sensors, the car {}", found, expected),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(error: std::io::Error) -> SnapshotError {
+        SnapshotError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(error: serde_json::Error) -> SnapshotError {
+        SnapshotError::Json(error)
+    }
+}
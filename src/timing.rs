@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+use crate::track::{Cone, Track};
+
+/// Emitted when the car crosses the timing line that ends a sector.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum TimingEvent {
+    Sector { lap: usize, sector: usize, time: f32 },
+    Lap { lap: usize, time: f32 },
+}
+
+/// Times of a completed lap in seconds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lap {
+    pub lap_time: f32,
+    pub sector_times: Vec<f32>,
+}
+
+/// Cars closer than this behind the start/finish line count as on it.
+const START_LINE_TOLERANCE: f32 = 0.01;    // m
+
+/// Lap and sector timing from crossings of the car's swept segment with
+/// the timing lines of the track. The lines have to be crossed in order, so
+/// cutting the track or driving backwards over a line does not count.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LapTimer {
+    lines: Vec<(Cone,Cone)>,
+    running: bool,              // false until a car starting behind the start/finish line crosses it
+    next_line: usize,
+    lap_start: f32,
+    sector_start: f32,
+    sector_times: Vec<f32>,
+    laps: Vec<Lap>,
+}
+
+impl LapTimer {
+    /// A car starting at `position` on or past the start/finish line starts
+    /// its first lap at 0.0 s, a car behind the line when it crosses it.
+    pub fn new(track: &Track, n_sectors: usize, position: (f32,f32)) -> LapTimer {
+        let lines = track.get_timing_lines(n_sectors.max(1));
+        let (start_x, start_y, direction) = track.get_start_pose();
+        let ahead = (position.0-start_x)*direction.cos() + (position.1-start_y)*direction.sin();
+        LapTimer {
+            running: ahead > -START_LINE_TOLERANCE,
+            next_line: 1 % lines.len(),
+            lines,
+            lap_start: 0.0,
+            sector_start: 0.0,
+            sector_times: vec![],
+            laps: vec![],
+        }
+    }
+
+    /// Checks the segment the car moved along from `from_time` to `to_time`
+    /// against the next timing line. The crossing time is interpolated.
+    pub fn update(&mut self, from: (f32,f32), to: (f32,f32), from_time: f32, to_time: f32) -> Option<TimingEvent> {
+        let next_line = if self.running { self.next_line } else { 0 };
+        let (line_start, line_end) = self.lines[next_line];
+        let t = segment_intersection(from, to, (line_start.0 as f32, line_start.1 as f32), (line_end.0 as f32, line_end.1 as f32))?;
+        let time = from_time + (to_time-from_time)*t;
+        if !self.running {
+            self.running = true;
+            self.lap_start = time;
+            self.sector_start = time;
+            return None;
+        }
+
+        let sector_time = time - self.sector_start;
+        self.sector_times.push(sector_time);
+        self.sector_start = time;
+        let event = if self.next_line == 0 {
+            let lap_time = time - self.lap_start;
+            self.laps.push(Lap {
+                lap_time,
+                sector_times: std::mem::take(&mut self.sector_times),
+            });
+            self.lap_start = time;
+            TimingEvent::Lap { lap: self.laps.len()-1, time: lap_time }
+        } else {
+            TimingEvent::Sector { lap: self.laps.len(), sector: self.sector_times.len()-1, time: sector_time }
+        };
+        self.next_line = (self.next_line + 1) % self.lines.len();
+        Some(event)
+    }
+
+    pub fn get_laps(&self) -> &[Lap] {
+        &self.laps
+    }
+
+    pub fn get_best_lap(&self) -> Option<&Lap> {
+        self.laps.iter().min_by(|a, b| a.lap_time.total_cmp(&b.lap_time))
+    }
+
+    /// Sector times of the running lap.
+    pub fn get_sector_times(&self) -> &[f32] {
+        &self.sector_times
+    }
+
+    /// 0.0 until the car crossed the start/finish line.
+    pub fn get_current_lap_time(&self, time: f32) -> f32 {
+        if self.running { time - self.lap_start } else { 0.0 }
+    }
+}
+
+/// Parameter in (0, 1] along `p1`→`p2` where it crosses the segment
+/// `q1`→`q2`. A segment starting on the line does not cross it.
+fn segment_intersection(p1: (f32,f32), p2: (f32,f32), q1: (f32,f32), q2: (f32,f32)) -> Option<f32> {
+    let r = (p2.0-p1.0, p2.1-p1.1);
+    let s = (q2.0-q1.0, q2.1-q1.1);
+    let denominator = r.0*s.1 - r.1*s.0;
+    if denominator == 0.0 {
+        return None;
+    }
+    let diff = (q1.0-p1.0, q1.1-p1.1);
+    let t = (diff.0*s.1 - diff.1*s.0)/denominator;
+    let u = (diff.0*r.1 - diff.1*r.0)/denominator;
+    if t > 0.0 && t <= 1.0 && (0.0..=1.0).contains(&u) {
+        Some(t)
+    } else {
+        None
+    }
+}
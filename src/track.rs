@@ -0,0 +1,256 @@
+use std::iter::zip;
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::grid::ConeGrid;
+
+/// Cone position in meters.
+pub type Cone = (f64,f64);
+
+pub const CONE_RADIUS: f64 = 0.35;      // m, at the base
+pub const CONE_HEIGHT: f64 = 0.325;     // m
+const GRID_CELL_SIZE: f64 = 5.0;        // m, about the distance between two cones along an edge
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ConeColor {
+    Blue,
+    Yellow,
+    Orange,
+}
+
+/// Identifies a cone by its color and index in the matching cone list.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ConeId {
+    pub color: ConeColor,
+    pub index: usize,
+}
+
+/// Cones of one generated circuit, indexed in a grid for range queries. The
+/// index is built with the track, so the cones can only be read, create a
+/// new track with `Track::new` to move them.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "TrackCones", into = "TrackCones")]
+pub struct Track {
+    blue_cones: Vec<Cone>,
+    yellow_cones: Vec<Cone>,
+    orange_cones: Vec<Cone>,
+    grid: ConeGrid,
+}
+
+/// What a track is saved as, the grid is rebuilt when loading.
+#[derive(Clone, Serialize, Deserialize)]
+struct TrackCones {
+    blue_cones: Vec<Cone>,
+    yellow_cones: Vec<Cone>,
+    orange_cones: Vec<Cone>,
+}
+
+impl From<TrackCones> for Track {
+    fn from(cones: TrackCones) -> Track {
+        Track::new(cones.blue_cones, cones.yellow_cones, cones.orange_cones)
+    }
+}
+
+impl From<Track> for TrackCones {
+    fn from(track: Track) -> TrackCones {
+        TrackCones {
+            blue_cones: track.blue_cones,
+            yellow_cones: track.yellow_cones,
+            orange_cones: track.orange_cones,
+        }
+    }
+}
+
+impl Track {
+    pub fn new(blue_cones: Vec<Cone>, yellow_cones: Vec<Cone>, orange_cones: Vec<Cone>) -> Track {
+        let mut track = Track {
+            blue_cones,
+            yellow_cones,
+            orange_cones,
+            grid: ConeGrid::default(),
+        };
+        track.grid = ConeGrid::new(track.iter_cones(), GRID_CELL_SIZE);
+        track
+    }
+
+    pub fn generate<R: Rng>(rng: &mut R) -> Track {
+        let (blue_cones, yellow_cones, orange_cones) = generate_cone_lists(rng);
+        Track::new(blue_cones, yellow_cones, orange_cones)
+    }
+
+    pub fn get_cones(&self, color: ConeColor) -> &[Cone] {
+        match color {
+            ConeColor::Blue => &self.blue_cones,
+            ConeColor::Yellow => &self.yellow_cones,
+            ConeColor::Orange => &self.orange_cones,
+        }
+    }
+
+    pub fn get_cone(&self, id: ConeId) -> Cone {
+        self.get_cones(id.color)[id.index]
+    }
+
+    /// All cones of the track with their ids.
+    pub fn iter_cones(&self) -> impl Iterator<Item = (ConeId, Cone)> + '_ {
+        [ConeColor::Blue, ConeColor::Yellow, ConeColor::Orange].into_iter().flat_map(move |color| {
+            self.get_cones(color).iter().enumerate().map(move |(index, cone)| (ConeId { color, index }, *cone))
+        })
+    }
+
+    /// Cones at most `radius` m from `center`, in no particular order.
+    pub fn query_cones(&self, center: (f32,f32), radius: f32) -> impl Iterator<Item = &(ConeId, Cone)> + '_ {
+        self.grid.query((center.0 as f64, center.1 as f64), radius as f64)
+    }
+
+    /// Cones at most `range` m from `origin` and less than half of `angle` rad
+    /// off `direction`, in no particular order.
+    pub fn query_sector(&self, origin: (f32,f32), direction: f32, angle: f32, range: f32) -> impl Iterator<Item = &(ConeId, Cone)> + '_ {
+        self.query_cones(origin, range).filter(move |(_, cone)| {
+            let bearing = (cone.1 as f32 - origin.1).atan2(cone.0 as f32 - origin.0) - direction;
+            bearing.sin().atan2(bearing.cos()).abs() < angle/2.0
+        })
+    }
+
+    /// Timing lines as pairs of cones in driving order: the start/finish
+    /// line between the orange cones first, then the lines between blue and
+    /// yellow cones that split the lap into `n_sectors` sectors.
+    pub fn get_timing_lines(&self, n_sectors: usize) -> Vec<(Cone,Cone)> {
+        let mut lines = vec![(self.orange_cones[0], self.orange_cones[1])];
+        let n_pairs = self.blue_cones.len().min(self.yellow_cones.len());
+        for sector in 1..n_sectors {
+            let index = n_pairs*sector/n_sectors;
+            lines.push((self.blue_cones[index], self.yellow_cones[index]));
+        }
+        lines
+    }
+
+    /// Position between the orange cones and the heading a car starts with,
+    /// pointing to the first pair of blue and yellow cones.
+    pub fn get_start_pose(&self) -> (f32,f32,f32) {
+        let x = (self.orange_cones[0].0+self.orange_cones[1].0)/2.0;
+        let y = (self.orange_cones[0].1+self.orange_cones[1].1)/2.0;
+        let next_x = (self.blue_cones[0].0+self.yellow_cones[0].0)/2.0;
+        let next_y = (self.blue_cones[0].1+self.yellow_cones[0].1)/2.0;
+        (x as f32, y as f32, f64::atan2(next_y-y, next_x-x) as f32)
+    }
+
+    /// Pose `distance` m behind the start pose along the centerline, through
+    /// the midpoints of the last blue and yellow pairs, heading in driving
+    /// direction. Distances beyond one lap end at the first pair.
+    pub fn get_pose_behind_start(&self, distance: f32) -> (f32,f32,f32) {
+        let start_pose = self.get_start_pose();
+        if distance <= 0.0 {
+            return start_pose;
+        }
+        let n_pairs = self.blue_cones.len().min(self.yellow_cones.len());
+        let midpoints = (0..n_pairs).rev().map(|i| {
+            (((self.blue_cones[i].0+self.yellow_cones[i].0)/2.0) as f32, ((self.blue_cones[i].1+self.yellow_cones[i].1)/2.0) as f32)
+        });
+        let mut ahead = (start_pose.0, start_pose.1);
+        let mut pose = start_pose;
+        let mut remaining = distance;
+        for behind in midpoints {
+            let (x_diff, y_diff) = (ahead.0-behind.0, ahead.1-behind.1);
+            let length = x_diff.hypot(y_diff);
+            let direction = y_diff.atan2(x_diff);
+            if remaining <= length {
+                let fraction = remaining/length;
+                return (ahead.0 - x_diff*fraction, ahead.1 - y_diff*fraction, direction);
+            }
+            remaining -= length;
+            pose = (behind.0, behind.1, direction);
+            ahead = behind;
+        }
+        pose
+    }
+}
+
+/// Generates a random closed track and returns the blue, yellow and orange cones.
+/// The two orange cones mark the start.
+pub fn generate_cone_lists<R: Rng>(rng: &mut R) -> (Vec<Cone>,Vec<Cone>,Vec<Cone>) {
+    
+    let center = (0.,0.);
+    let r1 = 40.0;
+    let r2 = 40.0;
+    let n: u32 = 8;
+    let (p, pitch) = get_circle_samples(center, r1, r2, n, rng);
+    let mut pitch_points: Vec<(f64,f64)> = vec![];
+
+    for ((x,y),pit) in zip(p.clone(), pitch.clone()) {
+        pitch_points.push((x+pit.cos()*0.5,y+pit.sin()*0.5));
+    }
+
+    let mut bezier: Vec<(f64, f64)> = vec![];
+    for i in 0..n-1 {
+        let mut bezier_points = bezier_curve(&p[i as usize], pitch[i as usize],&p[(i+1) as usize], pitch[(i+1) as usize], 100);
+        bezier.append(&mut bezier_points);
+    }
+    let mut bezier_points = bezier_curve(&p[(n-1) as usize], pitch[(n-1) as usize],&p[0], pitch[0], 100);
+    bezier.append(&mut bezier_points);
+
+
+
+    let mut last_point: (f64,f64) = (0.0,0.0);  //point[i-1]
+    let mut last_cone: (f64,f64) = (0.0,0.0);
+    let mut blue_cones: Vec<Cone> = vec![];
+    let mut yellow_cones: Vec<Cone> = vec![];
+    let orange_cones: Vec<Cone> = vec![(bezier[0].0-1.5,bezier[0].1), (bezier[0].0+1.5,bezier[0].1)];
+    for point in bezier.iter() {
+        if get_eukled_distance(last_cone,*point) > 4.0 {
+            if !((last_cone.0 == 0.0) && (last_cone.1 == 0.0 )) {
+                let mut radiant = f64::atan2(last_point.1-point.1, last_point.0-point.0)+ std::f64::consts::PI/2.0;
+                yellow_cones.push((point.0+radiant.cos()*1.5, point.1+radiant.sin()*1.5));
+                radiant -= std::f64::consts::PI;
+                blue_cones.push((point.0+radiant.cos()*1.5, point.1+radiant.sin()*1.5));    
+            }
+            last_cone = *point;
+
+        }
+        last_point = *point;
+    }
+    (blue_cones, yellow_cones, orange_cones)
+
+}
+
+pub fn get_eukled_distance(p1: (f64,f64), p2:(f64,f64)) -> f64 {
+    (f64::powf(p1.0-p2.0,2.0)+f64::powf(p1.1-p2.1,2.0)).sqrt()
+}
+
+/// Samples `n` points on an ellipse around `center` with random jitter and
+/// returns them together with the tangent direction at each point.
+pub fn get_circle_samples<R: Rng>(center:(f64,f64), r1: f64, r2:f64, n:u32, rng: &mut R) -> (Vec<(f64, f64)>, Vec<f64>){
+    let mut points: Vec<(f64, f64)>= vec![];
+    let mut pitch: Vec<f64>= vec![];
+
+    for i in 0..n {
+        let diff = std::f64::consts::PI*2.0/f64::from(n);
+        let factor = 0.2;
+        let rad = f64::from(i)*diff;
+        let x = (r1*rad.cos()+center.0) + rng.gen_range(-r1*factor..r1*factor);
+        let y = (r2*rad.sin()+center.1) + rng.gen_range(-r2*factor..r2*factor);
+        points.push((x,y));
+        pitch.push(rad+std::f64::consts::PI/2.0);
+    }
+    (points, pitch)
+}
+
+/// Cubic bezier curve with `n` points from `s_point` leaving in direction `s_rad`
+/// to `e_point` arriving in direction `e_rad`.
+pub fn bezier_curve(s_point: &(f64,f64), s_rad:f64, e_point: &(f64,f64), e_rad: f64, n:u32)-> Vec<(f64,f64)> {
+    let mut points: Vec<(f64,f64)> = vec![];
+    let dx: f64 = 1.0/f64::from(n);
+    let factor = 10.0;
+    let s_support: (f64,f64)  = (s_point.0+s_rad.cos()*factor,s_point.1+s_rad.sin()*factor);
+    let e_support: (f64,f64)  = (e_point.0+(e_rad-std::f64::consts::PI).cos()*factor,e_point.1+(e_rad-std::f64::consts::PI).sin()*factor);
+    for i in 0..n {
+        let t = dx*f64::from(i);
+        let x:f64 = s_point.0 + t*(-3.0*s_point.0+3.0*s_support.0) + t*t*( 3.0* s_point.0-6.0*s_support.0+ 3.0*e_support.0) + t*t*t* ( -s_point.0+ 3.0*s_support.0-3.0*e_support.0+e_point.0);
+        let y:f64 = s_point.1 + t*(-3.0*s_point.1+3.0*s_support.1) + t*t*( 3.0* s_point.1-6.0*s_support.1+ 3.0*e_support.1) + t*t*t* ( -s_point.1+ 3.0*s_support.1-3.0*e_support.1+e_point.1);
+        points.push((x,y));
+    }
+
+
+    points
+
+}
@@ -0,0 +1,258 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::agent::Agent;
+use crate::boundary::TrackBoundary;
+use crate::clock::SimClock;
+use crate::collision::Footprint;
+use crate::env::{Controller, Observation, StepInfo};
+use crate::params::VehicleParams;
+use crate::reward::RewardFunction;
+use crate::sensor::Sensor;
+use crate::snapshot::{SnapshotError, WorldSnapshot};
+use crate::track::Track;
+
+/// Several cars on one generated track, each driven by its own controller and
+/// scored by its own reward function. Cars start on a grid behind the start
+/// line and, unless collisions are disabled, hit each other.
+pub struct World {
+    track: Track,
+    boundary: TrackBoundary,
+    agents: Vec<Agent>,
+    controllers: Vec<Box<dyn Controller>>,
+    observations: Vec<Observation>,
+    infos: Vec<StepInfo>,
+    rewards: Vec<f32>,
+    clock: SimClock,
+    episode_length: f32,
+    end_on_off_track: bool,
+    car_collisions: bool,
+    grid_spacing: f32,
+    driving: Vec<bool>,         // reused by `step`
+    footprints: Vec<Footprint>, // reused by `step`
+}
+
+impl World {
+    /// `dt` is the physics step and `episode_length` the simulated seconds
+    /// after which an episode is done. The world starts reset with seed 0.
+    pub fn new(dt: f32, episode_length: f32) -> World {
+        let track = Track::generate(&mut StdRng::seed_from_u64(0));
+        World {
+            boundary: TrackBoundary::new(&track),
+            track,
+            agents: vec![],
+            controllers: vec![],
+            observations: vec![],
+            infos: vec![],
+            rewards: vec![],
+            clock: SimClock::new(dt),
+            episode_length,
+            end_on_off_track: false,
+            car_collisions: true,
+            grid_spacing: 6.0,
+            driving: vec![],
+            footprints: vec![],
+        }
+    }
+
+    /// Adds a car to the grid and returns its index. The car is placed at the
+    /// next reset.
+    pub fn add_car(&mut self, vehicle_params: VehicleParams, controller: Box<dyn Controller>, reward: Box<dyn RewardFunction>) -> usize {
+        self.agents.push(Agent::new(vehicle_params, reward, &self.track));
+        self.controllers.push(controller);
+        self.observations.push(Observation::default());
+        self.infos.push(StepInfo::default());
+        self.rewards.push(0.0);
+        self.agents.len()-1
+    }
+
+    /// Mounts a sensor on car `index` in addition to the ones of its vehicle
+    /// parameters, it takes its first sample with the next reset.
+    pub fn add_sensor(&mut self, index: usize, sensor: Box<dyn Sensor>) {
+        self.agents[index].add_sensor(sensor);
+    }
+
+    /// Subtracted from the reward of car `index` for every cone hit.
+    pub fn set_cone_penalty(&mut self, index: usize, cone_penalty: f32) {
+        self.agents[index].set_cone_penalty(cone_penalty);
+    }
+
+    /// Cars pass through each other when disabled, e.g. to compare controllers
+    /// side by side.
+    pub fn set_car_collisions(&mut self, car_collisions: bool) {
+        self.car_collisions = car_collisions;
+    }
+
+    /// Distance in m along the centerline between two cars on the starting
+    /// grid, 0.0 starts all cars at the same pose.
+    pub fn set_grid_spacing(&mut self, grid_spacing: f32) {
+        self.grid_spacing = grid_spacing;
+    }
+
+    /// Cars that leave the track stop and the episode ends once all have left.
+    pub fn set_end_on_off_track(&mut self, end_on_off_track: bool) {
+        self.end_on_off_track = end_on_off_track;
+    }
+
+    /// Generates a new track from `seed` and lines the cars up behind the start.
+    /// The same generator then draws the seed of the sensor noise of every
+    /// car, so neighbouring seeds do not hand the same noise to other cars.
+    pub fn reset(&mut self, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        self.track = Track::generate(&mut rng);
+        self.boundary = TrackBoundary::new(&self.track);
+        for (i, agent) in self.agents.iter_mut().enumerate() {
+            let pose = self.track.get_pose_behind_start(i as f32*self.grid_spacing);
+            agent.reset(&self.track, &self.boundary, pose, rng.gen());
+            agent.observe(&mut self.observations[i]);
+            self.infos[i] = StepInfo::default();
+            self.rewards[i] = 0.0;
+        }
+        self.clock.reset();
+    }
+
+    /// Moves every car that is still driving by one step. Rewards and step
+    /// infos are available through `get_reward` and `get_info` afterwards.
+    pub fn step(&mut self) {
+        let time = self.clock.get_time();
+        let dt = self.clock.get_dt();
+        self.driving.clear();
+        for i in 0..self.agents.len() {
+            let driving = self.is_driving(i);
+            self.driving.push(driving);
+        }
+        for (i, agent) in self.agents.iter_mut().enumerate() {
+            if self.driving[i] {
+                let action = self.controllers[i].act(&self.observations[i], dt);
+                self.infos[i] = agent.drive(action, &self.track, &self.boundary, time, dt);
+            }
+        }
+        self.clock.tick();
+
+        if self.car_collisions {
+            self.footprints.clear();
+            self.footprints.extend(self.agents.iter().map(|agent| agent.get_car().get_footprint()));
+            let footprints = &self.footprints;
+            for (i, agent) in self.agents.iter_mut().enumerate() {
+                let contacts = footprints.iter().enumerate()
+                    .filter(|(j, footprint)| *j != i && footprints[i].intersects(**footprint))
+                    .map(|(j, _)| j);
+                agent.update_car_contacts(contacts, &mut self.infos[i]);
+            }
+        }
+
+        for (i, agent) in self.agents.iter_mut().enumerate() {
+            if self.driving[i] {
+                agent.observe(&mut self.observations[i]);
+                self.rewards[i] = agent.get_reward(&self.infos[i]);
+            } else {
+                self.rewards[i] = 0.0;
+            }
+        }
+    }
+
+    /// Everything needed to continue the episode from this step with `restore`.
+    /// Fails if the state of a controller, reward function or sensor does not
+    /// serialize.
+    pub fn snapshot(&self) -> Result<WorldSnapshot, SnapshotError> {
+        Ok(WorldSnapshot {
+            track: self.track.clone(),
+            clock: self.clock,
+            episode_length: self.episode_length,
+            end_on_off_track: self.end_on_off_track,
+            car_collisions: self.car_collisions,
+            grid_spacing: self.grid_spacing,
+            agents: self.agents.iter().map(|agent| agent.snapshot()).collect::<Result<_, _>>()?,
+            controllers: self.controllers.iter().map(|controller| controller.snapshot()).collect::<Result<_, _>>()?,
+            observations: self.observations.clone(),
+            infos: self.infos.clone(),
+            rewards: self.rewards.clone(),
+        })
+    }
+
+    /// Continues from a snapshot. The world needs the same number of cars,
+    /// with controllers and reward functions of the types the snapshot was
+    /// taken with. Nothing changes if any part of the snapshot can not be read.
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) -> Result<(), SnapshotError> {
+        let n_cars = self.agents.len();
+        for found in [snapshot.agents.len(), snapshot.observations.len(), snapshot.infos.len(), snapshot.rewards.len()] {
+            if found != n_cars {
+                return Err(SnapshotError::CarCount { expected: n_cars, found });
+            }
+        }
+        if snapshot.controllers.len() != self.controllers.len() {
+            return Err(SnapshotError::ControllerCount { expected: self.controllers.len(), found: snapshot.controllers.len() });
+        }
+        let restored_agents = self.agents.iter()
+            .zip(snapshot.agents.iter())
+            .map(|(agent, agent_snapshot)| agent.load_snapshot(agent_snapshot))
+            .collect::<Result<Vec<_>, _>>()?;
+        let restored_controllers = self.controllers.iter()
+            .zip(snapshot.controllers.iter())
+            .map(|(controller, state)| controller.restore(state))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (agent, restored) in self.agents.iter_mut().zip(restored_agents) {
+            agent.apply_snapshot(restored);
+        }
+        for (controller, restored) in self.controllers.iter_mut().zip(restored_controllers) {
+            if let Some(restored) = restored {
+                *controller = restored;
+            }
+        }
+        self.track = snapshot.track.clone();
+        self.boundary = TrackBoundary::new(&self.track);
+        self.clock = snapshot.clock;
+        self.episode_length = snapshot.episode_length;
+        self.end_on_off_track = snapshot.end_on_off_track;
+        self.car_collisions = snapshot.car_collisions;
+        self.grid_spacing = snapshot.grid_spacing;
+        self.observations = snapshot.observations.clone();
+        self.infos = snapshot.infos.clone();
+        self.rewards = snapshot.rewards.clone();
+        Ok(())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.clock.get_time() >= self.episode_length || (0..self.agents.len()).all(|i| !self.is_driving(i))
+    }
+
+    /// Whether car `index` is still moved by `step`.
+    pub fn is_driving(&self, index: usize) -> bool {
+        !self.end_on_off_track || self.agents[index].get_boundary_status().on_track
+    }
+
+    pub fn get_n_cars(&self) -> usize {
+        self.agents.len()
+    }
+
+    pub fn get_agent(&self, index: usize) -> &Agent {
+        &self.agents[index]
+    }
+
+    /// Last observation of car `index`.
+    pub fn get_observation(&self, index: usize) -> &Observation {
+        &self.observations[index]
+    }
+
+    /// Events of car `index` in the last step.
+    pub fn get_info(&self, index: usize) -> &StepInfo {
+        &self.infos[index]
+    }
+
+    /// Reward of car `index` in the last step, 0.0 once the car stopped.
+    pub fn get_reward(&self, index: usize) -> f32 {
+        self.rewards[index]
+    }
+
+    pub fn get_track(&self) -> &Track {
+        &self.track
+    }
+
+    pub fn get_boundary(&self) -> &TrackBoundary {
+        &self.boundary
+    }
+
+    pub fn get_clock(&self) -> SimClock {
+        self.clock
+    }
+}